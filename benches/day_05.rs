@@ -0,0 +1,37 @@
+use advent_of_code_2018::day_05::{faster, parallel};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+/// A random polymer over a few unit types, so it reacts about as much as the
+/// puzzle input does.
+fn polymer(len: usize, mut seed: u64) -> Vec<u8> {
+    (0..len)
+        .map(|_| {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+
+            b"aAbBcCdD"[(seed % 8) as usize]
+        })
+        .collect()
+}
+
+fn reduce(c: &mut Criterion) {
+    let mut group = c.benchmark_group("day_05_reduce");
+
+    // The puzzle input is about 50 KB.
+    for &len in &[50_000, 1 << 20, 4 << 20, 16 << 20] {
+        let input = polymer(len, len as u64);
+        group.throughput(Throughput::Bytes(len as u64));
+        group.bench_with_input(BenchmarkId::new("faster", len), &input, |b, input| {
+            b.iter(|| faster(input))
+        });
+        group.bench_with_input(BenchmarkId::new("parallel", len), &input, |b, input| {
+            b.iter(|| parallel(input))
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, reduce);
+criterion_main!(benches);
@@ -0,0 +1,24 @@
+use advent_of_code_2018::day_09::{Circle, Deque, Links, MarbleGame, Ring};
+use criterion::{criterion_group, criterion_main, Criterion};
+
+fn bench_ring<R: Ring>(c: &mut Criterion, group: &str, name: &str, game: &MarbleGame) {
+    c.benchmark_group(group)
+        .sample_size(10)
+        .bench_function(name, |b| b.iter(|| game.play::<R>().unwrap()));
+}
+
+fn rings(c: &mut Criterion) {
+    let part_1 = MarbleGame::new(430, 71_588);
+    let part_2 = part_1.scaled(100).unwrap();
+
+    bench_ring::<Circle>(c, "day_09_part_1", "vec", &part_1);
+    bench_ring::<Deque>(c, "day_09_part_1", "deque", &part_1);
+    bench_ring::<Links>(c, "day_09_part_1", "links", &part_1);
+
+    // The `Vec` circle is quadratic, so it would take hours at this size.
+    bench_ring::<Deque>(c, "day_09_part_2", "deque", &part_2);
+    bench_ring::<Links>(c, "day_09_part_2", "links", &part_2);
+}
+
+criterion_group!(benches, rings);
+criterion_main!(benches);
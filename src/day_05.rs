@@ -1,6 +1,5 @@
-#![allow(dead_code)]
-
 use aoc_runner_derive::aoc;
+use rayon::prelude::*;
 
 #[derive(Debug, Clone, Copy)]
 pub enum Polarity {
@@ -34,14 +33,32 @@ impl Polarity {
     }
 }
 
-struct Polymer(u32, Vec<Option<Polarity>>);
+/// A polymer whose reacted units are left in place as tombstones and skipped
+/// over through `prev`/`next` links, so a reaction never shifts the backing
+/// storage.
+struct Polymer {
+    len: u32,
+    units: Vec<Option<Polarity>>,
+    prev: Vec<Option<usize>>,
+    next: Vec<Option<usize>>,
+}
 
 impl From<&str> for Polymer {
     fn from(input: &str) -> Polymer {
-        Polymer(
-            input.len() as u32,
-            input.chars().map(|c| Some(Polarity::new(c))).collect(),
-        )
+        let units = input
+            .chars()
+            .map(|c| Some(Polarity::new(c)))
+            .collect::<Vec<_>>();
+        let count = units.len();
+
+        Polymer {
+            len: count as u32,
+            units,
+            prev: (0..count).map(|i| i.checked_sub(1)).collect(),
+            next: (1..=count)
+                .map(|i| Some(i).filter(|i| *i < count))
+                .collect(),
+        }
     }
 }
 
@@ -50,65 +67,322 @@ impl std::fmt::Debug for Polymer {
         write!(
             f,
             "{:?}",
-            self.1.iter().filter(|f| f.is_some()).collect::<Vec<_>>()
+            self.units.iter().filter_map(|p| *p).collect::<Vec<_>>()
         )
     }
 }
 
-impl std::ops::Deref for Polymer {
-    type Target = [Option<Polarity>];
+impl Polymer {
+    fn len(&self) -> u32 {
+        self.len
+    }
+
+    fn first(&self) -> Option<usize> {
+        self.units.iter().position(Option::is_some)
+    }
+
+    /// Tombstones the adjacent pair `left`, `right` and links their
+    /// neighbours together, returning the unit to resume scanning from.
+    fn remove_pair(&mut self, left: usize, right: usize) -> Option<usize> {
+        let before = self.prev[left];
+        let after = self.next[right];
+
+        self.units[left] = None;
+        self.units[right] = None;
+        self.len -= 2;
+
+        if let Some(before) = before {
+            self.next[before] = after;
+        }
+
+        if let Some(after) = after {
+            self.prev[after] = before;
+        }
+
+        before.or(after)
+    }
+
+    fn react(&mut self) {
+        let mut cursor = self.first();
+
+        while let Some(idx1) = cursor {
+            let idx2 = match self.next[idx1] {
+                Some(idx2) => idx2,
+                None => break,
+            };
+
+            cursor = match (self.units[idx1], self.units[idx2]) {
+                (Some(current), Some(next)) if current.cancels(next) => {
+                    self.remove_pair(idx1, idx2)
+                }
+                _ => Some(idx2),
+            };
+        }
+    }
+}
+
+pub trait ReactionRule<T> {
+    fn reacts(&self, top: T, next: T) -> bool;
+}
 
-    fn deref(&self) -> &Self::Target {
-        &self.1
+impl<T, F: Fn(T, T) -> bool> ReactionRule<T> for F {
+    fn reacts(&self, top: T, next: T) -> bool {
+        self(top, next)
     }
 }
 
-impl std::ops::DerefMut for Polymer {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.1
+/// Units react when they are the same letter in opposite cases.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CasePair;
+
+impl ReactionRule<u8> for CasePair {
+    fn reacts(&self, top: u8, next: u8) -> bool {
+        top.is_ascii_alphabetic() && top ^ 0x20 == next
     }
 }
 
-impl Polymer {
-    fn next(&self, index: usize) -> Option<(usize, Polarity)> {
-        self.1[index..]
+impl ReactionRule<char> for CasePair {
+    fn reacts(&self, top: char, next: char) -> bool {
+        top.is_lowercase() != next.is_lowercase() && top.to_lowercase().eq(next.to_lowercase())
+    }
+}
+
+/// Units react when they form one of the given pairs, in either order.
+#[derive(Debug, Clone, Default)]
+pub struct SymbolPairs<T> {
+    pairs: Vec<(T, T)>,
+}
+
+impl<T: PartialEq> SymbolPairs<T> {
+    pub fn new(pairs: impl IntoIterator<Item = (T, T)>) -> SymbolPairs<T> {
+        SymbolPairs {
+            pairs: pairs.into_iter().collect(),
+        }
+    }
+}
+
+impl<T: PartialEq> ReactionRule<T> for SymbolPairs<T> {
+    fn reacts(&self, top: T, next: T) -> bool {
+        self.pairs
             .iter()
-            .enumerate()
-            .filter(|(_, p)| p.is_some())
-            .map(|(i, inner)| (i + index, inner.unwrap()))
-            .next()
+            .any(|(a, b)| (*a == top && *b == next) || (*a == next && *b == top))
     }
+}
 
-    fn len(&self) -> u32 {
-        self.0
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Reactor<R> {
+    rule: R,
+}
+
+impl<R> Reactor<R> {
+    pub fn new(rule: R) -> Reactor<R> {
+        Reactor { rule }
     }
 
-    fn remove(&mut self, index: usize) {
-        self[index] = None;
-        self.0 -= 1;
+    pub fn rule(&self) -> &R {
+        &self.rule
+    }
+
+    pub fn reduce<T: Copy>(&self, units: impl IntoIterator<Item = T>) -> Vec<T>
+    where
+        R: ReactionRule<T>,
+    {
+        let units = units.into_iter();
+        let mut stack = Vec::with_capacity(units.size_hint().0 / 3);
+
+        for n in units {
+            match stack.last() {
+                Some(&top) if self.rule.reacts(top, n) => {
+                    stack.pop();
+                }
+                _ => stack.push(n),
+            }
+        }
+
+        stack
+    }
+
+    /// Reduces `units` in independent chunks and stitches the residues back
+    /// together. Only matches `reduce` when the rule makes reduction
+    /// confluent, which is the case for `CasePair`.
+    ///
+    /// Each thread gets a few chunks so uneven ones balance out, but none
+    /// smaller than `MIN_CHUNK`, so short polymers run on fewer threads.
+    pub fn reduce_par<T: Copy + Send + Sync>(&self, units: &[T]) -> Vec<T>
+    where
+        R: ReactionRule<T> + Sync,
+    {
+        let chunks = rayon::current_num_threads() * CHUNKS_PER_THREAD;
+        let chunk_size = (units.len() / chunks).max(MIN_CHUNK);
+
+        units
+            .par_chunks(chunk_size)
+            .map(|chunk| self.reduce(chunk.iter().cloned()))
+            .reduce(Vec::new, |left, right| self.merge(left, right))
+    }
+
+    /// Joins two already reduced polymers, reacting units across the boundary.
+    pub fn merge<T: Copy>(&self, mut left: Vec<T>, right: Vec<T>) -> Vec<T>
+    where
+        R: ReactionRule<T>,
+    {
+        let mut reacted = 0;
+
+        while let (Some(&top), Some(&next)) = (left.last(), right.get(reacted)) {
+            if !self.rule.reacts(top, next) {
+                break;
+            }
+
+            left.pop();
+            reacted += 1;
+        }
+
+        left.extend_from_slice(&right[reacted..]);
+        left
+    }
+
+    /// Reduces `units` like `reduce`, yielding every reaction as it happens.
+    pub fn trace<'a, T: Copy>(&'a self, units: &'a [T]) -> Trace<'a, R, T> {
+        Trace {
+            rule: &self.rule,
+            units,
+            pos: 0,
+            stack: Vec::new(),
+            remaining: units.len(),
+        }
     }
 }
 
-#[aoc(day5, part1, faster)]
-pub fn faster(input: &[u8]) -> u32 {
-    let mut top = 0;
-    let mut v = Vec::with_capacity(input.len() / 3);
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Reaction<T> {
+    /// Positions of the reacting units in the original polymer.
+    pub left: usize,
+    pub right: usize,
+    pub units: (T, T),
+    /// Length of the whole polymer once this reaction has happened.
+    pub remaining: usize,
+}
 
-    for &n in input {
-        if top ^ 0x20 == n {
-            v.pop();
-            top = *v.last().unwrap_or(&0);
-        } else {
-            top = n;
-            v.push(n);
+pub struct Trace<'a, R, T> {
+    rule: &'a R,
+    units: &'a [T],
+    pos: usize,
+    stack: Vec<usize>,
+    remaining: usize,
+}
+
+impl<'a, R: ReactionRule<T>, T: Copy> Iterator for Trace<'a, R, T> {
+    type Item = Reaction<T>;
+
+    fn next(&mut self) -> Option<Reaction<T>> {
+        while let Some(&next) = self.units.get(self.pos) {
+            let right = self.pos;
+            self.pos += 1;
+
+            match self.stack.last() {
+                Some(&left) if self.rule.reacts(self.units[left], next) => {
+                    self.stack.pop();
+                    self.remaining -= 2;
+
+                    return Some(Reaction {
+                        left,
+                        right,
+                        units: (self.units[left], next),
+                        remaining: self.remaining,
+                    });
+                }
+                _ => self.stack.push(right),
+            }
         }
+
+        None
     }
+}
+
+/// Renders each intermediate polymer on its own line alongside the pair that
+/// reacts next, in the style of the puzzle's worked example.
+pub fn render_steps(polymer: &str) -> String {
+    use std::fmt::Write;
+
+    let units = polymer.trim().chars().collect::<Vec<_>>();
+    let mut alive = vec![true; units.len()];
+    let mut out = String::new();
+
+    let current = |alive: &[bool]| {
+        units
+            .iter()
+            .zip(alive)
+            .filter(|(_, alive)| **alive)
+            .map(|(c, _)| c)
+            .collect::<String>()
+    };
+
+    for reaction in Reactor::new(CasePair).trace(&units) {
+        let (a, b) = reaction.units;
+        writeln!(
+            out,
+            "{:width$}  '{}{}' reacts",
+            current(&alive),
+            a,
+            b,
+            width = units.len()
+        )
+        .unwrap();
+
+        alive[reaction.left] = false;
+        alive[reaction.right] = false;
+    }
+
+    writeln!(out, "{}", current(&alive)).unwrap();
+
+    out
+}
+
+/// Below this a chunk takes longer to hand to a thread than to reduce.
+const MIN_CHUNK: usize = 4 * 1024;
+const CHUNKS_PER_THREAD: usize = 4;
+
+#[aoc(day5, part1, faster)]
+pub fn faster(input: &[u8]) -> u32 {
+    Reactor::new(CasePair).reduce(input.iter().cloned()).len() as u32
+}
+
+#[aoc(day5, part1, parallel)]
+pub fn parallel(input: &[u8]) -> u32 {
+    Reactor::new(CasePair).reduce_par(input).len() as u32
+}
+
+/// Finds the unit type whose removal leaves the shortest polymer, returned as
+/// its lowercase byte along with that length.
+pub fn best_removal(input: &[u8]) -> (u8, u32) {
+    let reactor = Reactor::new(CasePair);
+    let reduced = reactor.reduce(input.iter().cloned());
+
+    (b'a'..b'z' + 1)
+        .into_par_iter()
+        .map(|unit| {
+            let len = reactor
+                .reduce(
+                    reduced
+                        .iter()
+                        .filter(|n| n.to_ascii_lowercase() != unit)
+                        .cloned(),
+                )
+                .len();
 
-    v.len() as u32
+            (unit, len as u32)
+        })
+        .min_by_key(|(_, len)| *len)
+        .unwrap()
 }
 
 #[aoc(day5, part2)]
 pub fn day_5_part_2(input: &[u8]) -> u32 {
+    best_removal(input).1
+}
+
+#[aoc(day5, part2, naive)]
+pub fn day_5_part_2_naive(input: &[u8]) -> u32 {
     (b'A'..=b'Z')
         .map(|c| {
             faster(
@@ -126,37 +400,141 @@ pub fn day_5_part_2(input: &[u8]) -> u32 {
 #[aoc(day5, part1)]
 pub fn basic_solution(input: &str) -> u32 {
     let mut polymer = Polymer::from(input.trim());
-    let mut last_len = polymer.len();
+    polymer.react();
+    polymer.len()
+}
 
-    loop {
-        let mut idx = 0;
+#[test]
+fn reactor_case_pair_test() {
+    let reactor = Reactor::new(CasePair);
 
-        'inner: while idx <= last_len {
-            let (idx1, current) = match polymer.next(idx as usize) {
-                Some((idx, current)) => (idx, current),
-                None => break 'inner,
-            };
+    assert_eq!(
+        reactor.reduce(b"dabAcCaCBAcCcaDA".iter().cloned()),
+        b"dabCBAcaDA".to_vec()
+    );
+    assert_eq!(
+        reactor.reduce("dabAcCaCBAcCcaDA".chars()),
+        "dabCBAcaDA".chars().collect::<Vec<_>>()
+    );
+    assert_eq!(reactor.reduce("ßẞxÄäX".chars()), Vec::<char>::new());
+    // Kelvin sign lowercases to k but is uppercase, just like K.
+    assert_eq!(reactor.reduce("K\u{212A}".chars()), ['K', '\u{212A}']);
+    assert_eq!(reactor.reduce("k\u{212A}".chars()), Vec::<char>::new());
+    assert_eq!(faster(b"dabAcCaCBAcCcaDA"), 10);
+}
 
-            let (idx2, next) = match polymer.next(idx1 + 1) {
-                Some((idx, next)) => (idx, next),
-                None => break 'inner,
-            };
+#[test]
+fn reactor_custom_rule_test() {
+    let brackets = Reactor::new(SymbolPairs::new(vec![('(', ')'), ('[', ']')]));
+    assert_eq!(
+        brackets.reduce("([)]([])".chars()),
+        vec!['(', '[', ')', ']']
+    );
 
-            if current.cancels(next) {
-                polymer.remove(idx1);
-                polymer.remove(idx2);
-                idx = 0;
-            } else {
-                idx += 1;
-            }
-        }
+    let sums = Reactor::new(|a: i32, b: i32| a + b == 0);
+    assert_eq!(sums.reduce(vec![1, 2, -2, 3, -3, -1, 4]), vec![4]);
+}
 
-        if last_len == polymer.len() {
-            break;
-        }
+#[cfg(test)]
+fn random_polymer(len: usize, mut seed: u64) -> Vec<u8> {
+    (0..len)
+        .map(|_| {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+
+            b"abcAB"[(seed % 5) as usize]
+        })
+        .collect()
+}
 
-        last_len = polymer.len();
+#[test]
+fn reactor_parallel_test() {
+    let reactor = Reactor::new(CasePair);
+
+    for (len, seed) in &[
+        (0, 1),
+        (17, 2),
+        (1000, 3),
+        (50_000, 5),
+        (4 * 1024 * 1024, 4),
+    ] {
+        let polymer = random_polymer(*len, *seed);
+
+        assert_eq!(
+            reactor.reduce_par(&polymer),
+            reactor.reduce(polymer.iter().cloned())
+        );
     }
 
-    polymer.len()
+    assert_eq!(parallel(b"dabAcCaCBAcCcaDA"), 10);
+    assert_eq!(
+        reactor.merge(b"dabAc".to_vec(), b"CaCBAcaDA".to_vec()),
+        b"dabCBAcaDA".to_vec()
+    );
+}
+
+#[test]
+fn best_removal_test() {
+    assert_eq!(best_removal(b"dabAcCaCBAcCcaDA"), (b'c', 4));
+    assert_eq!(day_5_part_2(b"dabAcCaCBAcCcaDA"), 4);
+
+    let polymer = random_polymer(10_000, 5);
+    assert_eq!(day_5_part_2(&polymer), day_5_part_2_naive(&polymer));
+}
+
+#[test]
+fn basic_solution_test() {
+    assert_eq!(basic_solution("dabAcCaCBAcCcaDA\n"), 10);
+    assert_eq!(basic_solution("aA"), 0);
+    assert_eq!(basic_solution("abBA"), 0);
+    assert_eq!(basic_solution(""), 0);
+
+    for seed in 1..50 {
+        let polymer = random_polymer(seed as usize * 37, seed);
+
+        assert_eq!(
+            basic_solution(std::str::from_utf8(&polymer).unwrap()),
+            faster(&polymer)
+        );
+    }
+}
+
+#[test]
+fn trace_test() {
+    let polymer = b"dabAcCaCBAcCcaDA";
+    let reactions = Reactor::new(CasePair).trace(polymer).collect::<Vec<_>>();
+
+    assert_eq!(
+        reactions,
+        vec![
+            Reaction {
+                left: 4,
+                right: 5,
+                units: (b'c', b'C'),
+                remaining: 14,
+            },
+            Reaction {
+                left: 3,
+                right: 6,
+                units: (b'A', b'a'),
+                remaining: 12,
+            },
+            Reaction {
+                left: 10,
+                right: 11,
+                units: (b'c', b'C'),
+                remaining: 10,
+            },
+        ]
+    );
+
+    assert_eq!(
+        render_steps("dabAcCaCBAcCcaDA"),
+        "dabAcCaCBAcCcaDA  'cC' reacts
+dabAaCBAcCcaDA    'Aa' reacts
+dabCBAcCcaDA      'cC' reacts
+dabCBAcaDA
+"
+    );
 }
@@ -1,25 +1,21 @@
 use aoc_runner_derive::{aoc, aoc_generator};
-use rayon::prelude::*;
-
-type Name = u32;
+use std::collections::VecDeque;
+use std::convert::TryFrom;
 
 #[derive(Debug, Clone, Copy, Default)]
 pub struct Point {
-    name: Option<Name>,
-    x: i32,
-    y: i32,
+    pub x: i32,
+    pub y: i32,
 }
 
 #[aoc_generator(day6)]
 pub fn generator(input: &str) -> Vec<Point> {
     input
         .lines()
-        .enumerate()
-        .map(|(idx, line)| {
+        .map(|line| {
             let mut parts = line.split(", ");
 
             Point {
-                name: Some(idx as u32),
                 x: parts.next().unwrap().parse().unwrap(),
                 y: parts.next().unwrap().parse().unwrap(),
             }
@@ -27,107 +23,675 @@ pub fn generator(input: &str) -> Vec<Point> {
         .collect()
 }
 
-#[aoc(day6, part1)]
-pub fn day_6_part_1(input: &[Point]) -> usize {
-    let mut grid = vec![Point::default(); 1000 * 1000];
-    let mut manhattans = Vec::with_capacity(input.len());
-    let mut areas = vec![0; input.len()];
-
-    for i in 0..1_000_000 {
-        let (x, y) = (i % 1000, i / 1000);
-        let mut current_point = Point {
-            name: None,
-            x: x as i32,
-            y: y as i32,
-        };
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Bounds {
+    pub min_x: i32,
+    pub min_y: i32,
+    pub max_x: i32,
+    pub max_y: i32,
+}
 
-        manhattans.extend(input.iter().map(|point| manhattan(current_point, *point)));
-        manhattans.sort_unstable_by_key(|(_, p)| *p);
+impl Bounds {
+    pub fn of(points: &[Point]) -> Bounds {
+        Bounds {
+            min_x: points.iter().map(|p| p.x).min().unwrap_or(0),
+            min_y: points.iter().map(|p| p.y).min().unwrap_or(0),
+            max_x: points.iter().map(|p| p.x).max().unwrap_or(0),
+            max_y: points.iter().map(|p| p.y).max().unwrap_or(0),
+        }
+    }
 
-        let two_contest = manhattans[0].1 == manhattans[1].1;
+    pub fn expand(self, margin: i32) -> Bounds {
+        Bounds {
+            min_x: self.min_x - margin,
+            min_y: self.min_y - margin,
+            max_x: self.max_x + margin,
+            max_y: self.max_y + margin,
+        }
+    }
 
-        // TODO: optimize not to even need the grid
+    pub fn width(&self) -> usize {
+        (self.max_x - self.min_x + 1) as usize
+    }
 
-        grid[y * 1000 + x] = if two_contest {
-            current_point
-        } else {
-            let (name, _) = manhattans[0];
-            current_point.name = Some(name);
-            areas[name as usize] += 1;
+    pub fn height(&self) -> usize {
+        (self.max_y - self.min_y + 1) as usize
+    }
 
-            current_point
-        };
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        x >= self.min_x && x <= self.max_x && y >= self.min_y && y <= self.max_y
+    }
+
+    pub fn on_border(&self, x: i32, y: i32) -> bool {
+        x == self.min_x || x == self.max_x || y == self.min_y || y == self.max_y
+    }
+
+    fn index(&self, x: i32, y: i32) -> usize {
+        (y - self.min_y) as usize * self.width() + (x - self.min_x) as usize
+    }
+
+    fn coords(&self, index: usize) -> (i32, i32) {
+        (
+            self.min_x + (index % self.width()) as i32,
+            self.min_y + (index / self.width()) as i32,
+        )
+    }
+}
+
+pub trait Metric {
+    /// Distance covered by an offset of `dx`, `dy`. Must never be shorter than
+    /// the larger of the two, which keeps `SafeRegion::window` complete.
+    fn distance(&self, dx: i32, dy: i32) -> i64;
+
+    /// The per-axis term `f` when `distance(dx, dy) == f(dx) + f(dy)`, which
+    /// lets totals over many points be summed per row and column.
+    fn axis_term(&self, _delta: i32) -> Option<i64> {
+        None
+    }
+
+    /// The moves of a grid walk whose shortest path length is this distance,
+    /// which lets regions be flood filled instead of compared cell by cell.
+    fn steps(&self) -> Option<&'static [(i32, i32)]> {
+        None
+    }
+
+    fn between(&self, p1: Point, p2: Point) -> i64 {
+        self.distance(p1.x - p2.x, p1.y - p2.y)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Manhattan;
+
+impl Metric for Manhattan {
+    fn distance(&self, dx: i32, dy: i32) -> i64 {
+        i64::from(dx.abs()) + i64::from(dy.abs())
+    }
 
-        manhattans.clear();
+    fn axis_term(&self, delta: i32) -> Option<i64> {
+        Some(i64::from(delta.abs()))
     }
 
-    let mut disqualified = Vec::new();
+    fn steps(&self) -> Option<&'static [(i32, i32)]> {
+        Some(&[(-1, 0), (1, 0), (0, -1), (0, 1)])
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Chebyshev;
+
+impl Metric for Chebyshev {
+    fn distance(&self, dx: i32, dy: i32) -> i64 {
+        i64::from(dx.abs().max(dy.abs()))
+    }
+
+    fn steps(&self) -> Option<&'static [(i32, i32)]> {
+        Some(&[
+            (-1, -1),
+            (0, -1),
+            (1, -1),
+            (-1, 0),
+            (1, 0),
+            (-1, 1),
+            (0, 1),
+            (1, 1),
+        ])
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EuclideanSquared;
+
+impl Metric for EuclideanSquared {
+    fn distance(&self, dx: i32, dy: i32) -> i64 {
+        let (dx, dy) = (i64::from(dx), i64::from(dy));
+        dx * dx + dy * dy
+    }
+
+    fn axis_term(&self, delta: i32) -> Option<i64> {
+        Some(i64::from(delta) * i64::from(delta))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Owner {
+    Unclaimed,
+    /// Owned by the point at this index in the slice the map was built from.
+    Single(usize),
+    Tie,
+}
+
+impl Owner {
+    fn merge(self, other: Owner) -> Owner {
+        match (self, other) {
+            (Owner::Unclaimed, owner) | (owner, Owner::Unclaimed) => owner,
+            (Owner::Single(n1), Owner::Single(n2)) if n1 == n2 => self,
+            _ => Owner::Tie,
+        }
+    }
+}
+
+/// The owner of every cell within some bounds, i.e. a Voronoi diagram under
+/// some metric clipped to those bounds.
+#[derive(Debug, Clone)]
+pub struct RegionMap {
+    bounds: Bounds,
+    cells: Vec<Owner>,
+}
+
+impl RegionMap {
+    pub fn new(points: &[Point], bounds: Bounds, metric: &impl Metric) -> RegionMap {
+        // A source outside the bounds can't grow its region into them, so
+        // flood filling needs every source inside.
+        let inside = points.iter().all(|p| bounds.contains(p.x, p.y));
+
+        match metric.steps() {
+            Some(steps) if inside => RegionMap::flood_fill(points, bounds, steps),
+            _ => RegionMap::nearest(points, bounds, metric),
+        }
+    }
+
+    /// Grows every region outwards one step at a time from its source. With
+    /// nothing in the way the BFS distance is the metric's distance, and a
+    /// cell reached at the same distance from two regions becomes a tie which
+    /// spreads on like any other owner.
+    fn flood_fill(points: &[Point], bounds: Bounds, steps: &[(i32, i32)]) -> RegionMap {
+        let mut cells = vec![Owner::Unclaimed; bounds.width() * bounds.height()];
+        let mut distances = vec![u32::MAX; cells.len()];
+        let mut queue = VecDeque::new();
+
+        for (source, point) in points.iter().enumerate() {
+            let idx = bounds.index(point.x, point.y);
+
+            if distances[idx] != 0 {
+                distances[idx] = 0;
+                queue.push_back(idx);
+            }
+
+            cells[idx] = cells[idx].merge(Owner::Single(source));
+        }
+
+        while let Some(idx) = queue.pop_front() {
+            let (x, y) = bounds.coords(idx);
+            let distance = distances[idx] + 1;
 
-    for y in 0..1000 {
-        if y == 0 || y == 999 {
-            for x in 0..1000 {
-                match grid[y * 1000 + x].name {
-                    Some(name) if !disqualified.contains(&name) => disqualified.push(name),
-                    _ => {}
+            for &(dx, dy) in steps {
+                let (nx, ny) = (x + dx, y + dy);
+
+                if !bounds.contains(nx, ny) {
+                    continue;
+                }
+
+                let nidx = bounds.index(nx, ny);
+
+                if distances[nidx] == u32::MAX {
+                    distances[nidx] = distance;
+                    queue.push_back(nidx);
+                }
+
+                if distances[nidx] == distance {
+                    cells[nidx] = cells[nidx].merge(cells[idx]);
                 }
             }
+        }
+
+        RegionMap { bounds, cells }
+    }
+
+    fn nearest(points: &[Point], bounds: Bounds, metric: &impl Metric) -> RegionMap {
+        let cells = (0..bounds.width() * bounds.height())
+            .map(|idx| {
+                let (x, y) = bounds.coords(idx);
+                let mut closest = (i64::MAX, Owner::Unclaimed);
+
+                for (source, point) in points.iter().enumerate() {
+                    let distance = metric.distance(x - point.x, y - point.y);
+                    let owner = Owner::Single(source);
+
+                    if distance < closest.0 {
+                        closest = (distance, owner);
+                    } else if distance == closest.0 {
+                        closest.1 = closest.1.merge(owner);
+                    }
+                }
+
+                closest.1
+            })
+            .collect();
+
+        RegionMap { bounds, cells }
+    }
+
+    pub fn bounds(&self) -> Bounds {
+        self.bounds
+    }
+
+    pub fn get(&self, x: i32, y: i32) -> Owner {
+        if self.bounds.contains(x, y) {
+            self.cells[self.bounds.index(x, y)]
         } else {
-            match grid[y * 1000].name {
-                Some(name) if !disqualified.contains(&name) => disqualified.push(name),
-                _ => {}
+            Owner::Unclaimed
+        }
+    }
+
+    pub fn areas(&self, regions: usize) -> Vec<usize> {
+        let mut areas = vec![0; regions];
+
+        for cell in &self.cells {
+            if let Owner::Single(source) = cell {
+                areas[*source] += 1;
             }
+        }
+
+        areas
+    }
+
+    /// Marks regions which reach the border. Under Manhattan distance these
+    /// keep going forever, as stepping straight outwards moves away from every
+    /// source equally; other metrics need a wider margin to approximate it.
+    pub fn infinite(&self, regions: usize) -> Vec<bool> {
+        let mut infinite = vec![false; regions];
+
+        for (idx, cell) in self.cells.iter().enumerate() {
+            let (x, y) = self.bounds.coords(idx);
 
-            match grid[y * 1000 + 999].name {
-                Some(name) if !disqualified.contains(&name) => disqualified.push(name),
-                _ => {}
+            if let Owner::Single(source) = cell {
+                if self.bounds.on_border(x, y) {
+                    infinite[*source] = true;
+                }
             }
         }
+
+        infinite
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AreaReport {
+    /// Cells owned by each point, indexed like the points.
+    pub areas: Vec<usize>,
+    pub infinite: Vec<bool>,
+    /// The point with the largest finite area and that area, preferring the
+    /// earliest point on a tie.
+    pub winner: Option<(usize, usize)>,
+}
+
+/// Points outside `bounds` count as infinite, as their regions don't fit in
+/// the bounds either.
+pub fn largest_finite_area(points: &[Point], bounds: Bounds) -> AreaReport {
+    let map = RegionMap::new(points, bounds, &Manhattan);
+    let areas = map.areas(points.len());
+    let mut infinite = map.infinite(points.len());
+
+    for (idx, point) in points.iter().enumerate() {
+        infinite[idx] |= !bounds.contains(point.x, point.y);
+    }
+
+    let winner = areas
+        .iter()
+        .zip(&infinite)
+        .enumerate()
+        .filter(|(_, (_, infinite))| !**infinite)
+        .map(|(source, (area, _))| (source, *area))
+        .fold(
+            None,
+            |best: Option<(usize, usize)>, (source, area)| match best {
+                Some((_, best_area)) if best_area >= area => best,
+                _ => Some((source, area)),
+            },
+        );
+
+    AreaReport {
+        areas,
+        infinite,
+        winner,
+    }
+}
+
+#[aoc(day6, part1)]
+pub fn day_6_part_1(input: &[Point]) -> usize {
+    let (_, area) = largest_finite_area(input, Bounds::of(input).expand(1))
+        .winner
+        .unwrap();
+
+    area
+}
+
+#[derive(Debug, Clone)]
+enum Totals {
+    Separable { x_sums: Vec<i64>, y_sums: Vec<i64> },
+    Cells(Vec<i64>),
+}
+
+/// Every cell whose total distance to all points is below a threshold.
+///
+/// When the metric splits into an x part and a y part, each of which only
+/// depends on one coordinate, both are computed once per column and row
+/// instead of once per cell.
+#[derive(Debug, Clone)]
+pub struct SafeRegion {
+    bounds: Bounds,
+    threshold: i64,
+    totals: Totals,
+}
+
+impl SafeRegion {
+    /// `None` when the threshold is so large that the window to scan doesn't
+    /// fit in `i32` coordinates.
+    pub fn new(points: &[Point], threshold: i32) -> Option<SafeRegion> {
+        SafeRegion::with_metric(points, threshold, &Manhattan)
+    }
+
+    pub fn with_metric(
+        points: &[Point],
+        threshold: i32,
+        metric: &impl Metric,
+    ) -> Option<SafeRegion> {
+        let bounds = SafeRegion::window(points, threshold)?;
+
+        let totals = if metric.axis_term(0).is_some() {
+            let sums = |range: std::ops::RangeInclusive<i32>, coord: fn(&Point) -> i32| {
+                range
+                    .map(|v| {
+                        points
+                            .iter()
+                            .map(|p| metric.axis_term(v - coord(p)).unwrap())
+                            .sum()
+                    })
+                    .collect::<Vec<i64>>()
+            };
+
+            Totals::Separable {
+                x_sums: sums(bounds.min_x..=bounds.max_x, |p| p.x),
+                y_sums: sums(bounds.min_y..=bounds.max_y, |p| p.y),
+            }
+        } else {
+            Totals::Cells(
+                (0..bounds.width() * bounds.height())
+                    .map(|idx| {
+                        let (x, y) = bounds.coords(idx);
+                        points
+                            .iter()
+                            .map(|p| metric.distance(x - p.x, y - p.y))
+                            .sum()
+                    })
+                    .collect(),
+            )
+        };
+
+        Some(SafeRegion {
+            bounds,
+            threshold: i64::from(threshold),
+            totals,
+        })
+    }
+
+    /// Outside the points' bounding box every step away adds at least one per
+    /// point to the total, so nothing further than `threshold / points` beyond
+    /// it can be under the threshold. `None` if that window, or its width or
+    /// height, doesn't fit in an `i32`.
+    pub fn window(points: &[Point], threshold: i32) -> Option<Bounds> {
+        let inner = Bounds::of(points);
+        let margin = i64::from(threshold.max(0)) / points.len().max(1) as i64 + 1;
+        let fit = |v: i64| i32::try_from(v).ok();
+
+        let bounds = Bounds {
+            min_x: fit(i64::from(inner.min_x) - margin)?,
+            min_y: fit(i64::from(inner.min_y) - margin)?,
+            max_x: fit(i64::from(inner.max_x) + margin)?,
+            max_y: fit(i64::from(inner.max_y) + margin)?,
+        };
+        fit(i64::from(bounds.max_x) - i64::from(bounds.min_x) + 1)?;
+        fit(i64::from(bounds.max_y) - i64::from(bounds.min_y) + 1)?;
+
+        Some(bounds)
+    }
+
+    pub fn bounds(&self) -> Bounds {
+        self.bounds
+    }
+
+    pub fn total_distance(&self, x: i32, y: i32) -> Option<i64> {
+        if !self.bounds.contains(x, y) {
+            return None;
+        }
+
+        Some(match &self.totals {
+            Totals::Separable { x_sums, y_sums } => {
+                x_sums[(x - self.bounds.min_x) as usize] + y_sums[(y - self.bounds.min_y) as usize]
+            }
+            Totals::Cells(cells) => cells[self.bounds.index(x, y)],
+        })
+    }
+
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        self.total_distance(x, y)
+            .is_some_and(|total| total < self.threshold)
     }
 
-    grid.par_iter()
-        .filter(|p| p.name.is_some() && !disqualified.contains(&p.name.unwrap()))
-        .map(|point| areas[point.name.unwrap() as usize])
-        .max()
-        .unwrap()
+    pub fn size(&self) -> usize {
+        match &self.totals {
+            Totals::Separable { x_sums, y_sums } => {
+                let mut y_sums = y_sums.clone();
+                y_sums.sort_unstable();
+
+                x_sums
+                    .iter()
+                    .map(|x_sum| y_sums.partition_point(|y_sum| x_sum + y_sum < self.threshold))
+                    .sum()
+            }
+            Totals::Cells(cells) => cells.iter().filter(|t| **t < self.threshold).count(),
+        }
+    }
 }
 
 #[aoc(day6, part2)]
 pub fn day_6_part_2(input: &[Point]) -> usize {
-    (0..1000)
-        .map(move |x| (0..1000).map(move |y| (x, y)))
-        .flatten()
-        .map(|(x, y)| {
-            input
-                .iter()
-                .map(|p| manhattan(Point { name: None, x, y }, *p).1)
-                .sum::<i32>() as usize
+    SafeRegion::new(input, 10000).unwrap().size()
+}
+
+/// How many letters it takes to give each of `sources` its own label.
+fn label_width(sources: usize) -> usize {
+    let mut width = 1;
+    let mut labels = 26;
+
+    while labels < sources {
+        width += 1;
+        labels *= 26;
+    }
+
+    width
+}
+
+/// `a` to `z` for a width of one, then `aa`, `ab` and so on.
+fn label(mut source: usize, width: usize) -> String {
+    let mut label = vec![b'a'; width];
+
+    for letter in label.iter_mut().rev() {
+        *letter += (source % 26) as u8;
+        source /= 26;
+    }
+
+    String::from_utf8(label).unwrap()
+}
+
+fn colour(source: usize) -> [u8; 3] {
+    let hash = (source as u32).wrapping_mul(2_654_435_761);
+
+    [
+        64 + (hash >> 8) as u8 % 192,
+        64 + (hash >> 16) as u8 % 192,
+        64 + (hash >> 24) as u8 % 192,
+    ]
+}
+
+enum Mark {
+    Region(usize),
+    Fill(char),
+}
+
+/// Every cell is as wide as the labels, so with more than 26 sources each
+/// takes two letters or more and no two regions share a label.
+fn draw(points: &[Point], bounds: Bounds, cell: impl Fn(i32, i32) -> Mark) -> String {
+    let width = label_width(points.len());
+    let mut grid = (bounds.min_y..=bounds.max_y)
+        .map(|y| {
+            (bounds.min_x..=bounds.max_x)
+                .map(|x| match cell(x, y) {
+                    Mark::Region(source) => label(source, width),
+                    Mark::Fill(fill) => fill.to_string().repeat(width),
+                })
+                .collect::<Vec<_>>()
         })
-        .filter(|x| *x < 10000)
-        .count()
+        .collect::<Vec<_>>();
+
+    for (source, point) in points.iter().enumerate() {
+        if bounds.contains(point.x, point.y) {
+            grid[(point.y - bounds.min_y) as usize][(point.x - bounds.min_x) as usize] =
+                label(source, width).to_ascii_uppercase();
+        }
+    }
+
+    grid.into_iter().map(|row| row.concat() + "\n").collect()
 }
 
-fn manhattan(p1: Point, p2: Point) -> (Name, i32) {
-    (p2.name.unwrap(), (p1.x - p2.x).abs() + (p1.y - p2.y).abs())
+/// Draws the map like the puzzle does: sources in uppercase, the cells they
+/// own in lowercase and ties as `.`.
+pub fn render(points: &[Point], map: &RegionMap) -> String {
+    draw(points, map.bounds(), |x, y| match map.get(x, y) {
+        Owner::Single(source) => Mark::Region(source),
+        _ => Mark::Fill('.'),
+    })
+}
+
+/// Draws the sources over the safe region, which is marked with `#`.
+pub fn render_safe(points: &[Point], region: &SafeRegion, bounds: Bounds) -> String {
+    draw(points, bounds, |x, y| {
+        if region.contains(x, y) {
+            Mark::Fill('#')
+        } else {
+            Mark::Fill('.')
+        }
+    })
+}
+
+/// Writes the map as a binary PPM with one pixel per cell and a colour per
+/// region. Ties are black, sources white, and cells in `safe` are lightened.
+pub fn write_ppm(
+    out: &mut impl std::io::Write,
+    points: &[Point],
+    map: &RegionMap,
+    safe: Option<&SafeRegion>,
+) -> std::io::Result<()> {
+    let bounds = map.bounds();
+    write!(out, "P6\n{} {}\n255\n", bounds.width(), bounds.height())?;
+
+    for y in bounds.min_y..=bounds.max_y {
+        for x in bounds.min_x..=bounds.max_x {
+            let mut pixel = match map.get(x, y) {
+                Owner::Single(source) => colour(source),
+                _ => [0, 0, 0],
+            };
+
+            if safe.is_some_and(|safe| safe.contains(x, y)) {
+                pixel.iter_mut().for_each(|c| *c = *c / 2 + 128);
+            }
+
+            if points.iter().any(|p| p.x == x && p.y == y) {
+                pixel = [255, 255, 255];
+            }
+
+            out.write_all(&pixel)?;
+        }
+    }
+
+    Ok(())
 }
 
 #[test]
-fn manhattan_test() {
-    let p1 = Point {
-        name: None,
-        x: 10,
-        y: 20,
-    };
-    let p2 = Point {
-        name: Some(1),
-        x: 10,
-        y: 20,
-    };
+fn metric_test() {
+    let p1 = Point { x: 10, y: 20 };
+    let p2 = Point { x: 13, y: 16 };
+
+    assert_eq!(Manhattan.between(p1, p1), 0);
+    assert_eq!(Manhattan.between(p1, p2), 7);
+    assert_eq!(Chebyshev.between(p1, p2), 4);
+    assert_eq!(EuclideanSquared.between(p1, p2), 25);
+
+    let points = [p1, p2];
+    let map = RegionMap::new(&points, Bounds::of(&points), &Manhattan);
+    assert_eq!(map.get(10, 20), Owner::Single(0));
+    assert_eq!(map.get(13, 16), Owner::Single(1));
+    assert_eq!(render(&points, &map).lines().next(), Some("bbbB"));
+}
 
-    assert_eq!(manhattan(p1, p2), (1, 0));
+#[test]
+fn metric_regions_test() {
+    let input = "1, 1
+1, 6
+8, 3
+3, 4
+5, 5
+8, 9";
+
+    let points = generator(input);
+    let bounds = Bounds::of(&points).expand(3);
+
+    let flood = RegionMap::new(&points, bounds, &Chebyshev);
+    let scan = RegionMap::nearest(&points, bounds, &Chebyshev);
+    assert_eq!(flood.cells, scan.cells);
+
+    let flood = RegionMap::new(&points, bounds, &Manhattan);
+    let scan = RegionMap::nearest(&points, bounds, &Manhattan);
+    assert_eq!(flood.cells, scan.cells);
+
+    let euclidean = RegionMap::new(&points, bounds, &EuclideanSquared);
+    assert_eq!(euclidean.get(4, 4), Owner::Single(3));
+    assert_eq!(euclidean.get(2, 1), Owner::Single(0));
+
+    let safe = SafeRegion::with_metric(&points, 60, &Chebyshev).unwrap();
+    let separable = SafeRegion::with_metric(&points, 60, &EuclideanSquared).unwrap();
+    for y in -20..30 {
+        for x in -20..30 {
+            let total = |metric: &dyn Metric| {
+                points
+                    .iter()
+                    .map(|p| metric.distance(x - p.x, y - p.y))
+                    .sum::<i64>()
+            };
+
+            assert_eq!(safe.contains(x, y), total(&Chebyshev) < 60);
+            assert_eq!(separable.contains(x, y), total(&EuclideanSquared) < 60);
+        }
+    }
 }
 
 #[test]
-fn smol_test() {
+fn flood_fill_test() {
+    let input = "1, 1
+1, 6
+8, 3
+3, 4
+5, 5
+8, 9";
+
+    let points = generator(input);
+    let map = RegionMap::new(&points, Bounds::of(&points).expand(1), &Manhattan);
+
+    assert_eq!(map.get(0, 0), Owner::Single(0));
+    assert_eq!(map.get(1, 4), Owner::Tie);
+    assert_eq!(map.get(5, 0), Owner::Tie);
+    assert_eq!(map.get(5, 2), Owner::Single(4));
+    assert_eq!(map.areas(points.len())[3..5], [9, 17]);
+    assert_eq!(
+        map.infinite(points.len()),
+        [true, true, true, false, false, true]
+    );
+    assert_eq!(day_6_part_1(&points), 17);
+}
+
+#[test]
+fn tight_bounds_test() {
     let input = "1, 1
 1, 6
 8, 3
@@ -136,88 +700,196 @@ fn smol_test() {
 8, 9";
 
     let points = generator(input);
+    let bounds = Bounds {
+        min_x: 0,
+        min_y: 0,
+        max_x: 4,
+        max_y: 4,
+    };
 
-    let mut grid = vec![Point::default(); 10 * 10];
-    let mut manhattans = Vec::with_capacity(input.len());
-    let mut areas = vec![0; points.len()];
+    let map = RegionMap::new(&points, bounds, &Manhattan);
+    assert_eq!(
+        map.cells,
+        RegionMap::nearest(&points, bounds, &Manhattan).cells
+    );
+    // Tied between 1, 6 outside the bounds and 3, 4 inside them.
+    assert_eq!(map.get(0, 4), Owner::Tie);
 
-    for i in 0..100 {
-        let (x, y) = (i % 10, i / 10);
+    let map = RegionMap::new(&points, bounds, &Chebyshev);
+    assert_eq!(
+        map.cells,
+        RegionMap::nearest(&points, bounds, &Chebyshev).cells
+    );
 
-        let mut current_point = Point {
-            name: None,
-            x: x as i32,
-            y: y as i32,
-        };
+    let report = largest_finite_area(&points, bounds);
+    assert_eq!(report.infinite, [true, true, true, true, true, true]);
+    assert_eq!(report.winner, None);
+}
 
-        manhattans.extend(points.iter().map(|point| manhattan(current_point, *point)));
-        manhattans.sort_unstable_by_key(|(_, p)| *p);
+#[test]
+fn safe_region_test() {
+    let input = "1, 1
+1, 6
+8, 3
+3, 4
+5, 5
+8, 9";
 
-        let two_contest = manhattans[0].1 == manhattans[1].1;
+    let points = generator(input);
+    let region = SafeRegion::new(&points, 32).unwrap();
 
-        grid[y * 10 + x] = if two_contest {
-            current_point
-        } else {
-            let (name, _) = manhattans[0];
-            areas[name as usize] += 1;
-            current_point.name = Some(name);
+    assert_eq!(region.total_distance(4, 3), Some(30));
+    assert!(region.contains(4, 3));
+    assert!(!region.contains(1, 1));
+    assert_eq!(region.size(), 16);
 
-            current_point
-        };
+    let brute_force = (-100..100)
+        .flat_map(|x| (-100..100).map(move |y| (x, y)))
+        .filter(|&(x, y)| {
+            points
+                .iter()
+                .map(|p| (x - p.x).abs() + (y - p.y).abs())
+                .sum::<i32>()
+                < 500
+        })
+        .count();
 
-        println!("{} {:?}", y * 10 + x, grid[y * 10 + x]);
+    assert_eq!(SafeRegion::new(&points, 500).unwrap().size(), brute_force);
+}
 
-        /*print!(
-            "{}",
-            current_point
-                .name
-                .map(|n| ((n as u8 + b'A') as char).to_string())
-                .unwrap_or_else(|| ".".to_string())
-        );
+#[test]
+fn render_test() {
+    let input = "1, 1
+1, 6
+8, 3
+3, 4
+5, 5
+8, 9";
 
-        if x == 9 {
-            println!();
-        }*/
+    let points = generator(input);
+    let bounds = Bounds {
+        min_x: 0,
+        min_y: 0,
+        max_x: 9,
+        max_y: 9,
+    };
 
-        manhattans.clear();
-    }
+    assert_eq!(
+        render(&points, &RegionMap::new(&points, bounds, &Manhattan)),
+        "aaaaa.cccc
+aAaaa.cccc
+aaaddecccc
+aadddeccCc
+..dDdeeccc
+bb.deEeecc
+bBb.eeee..
+bbb.eeefff
+bbb.eeffff
+bbb.ffffFf
+"
+    );
 
-    let mut disqualified = Vec::new();
+    assert_eq!(
+        render_safe(&points, &SafeRegion::new(&points, 32).unwrap(), bounds),
+        "..........
+.A........
+..........
+...###..C.
+..#D###...
+..###E#...
+.B.###....
+..........
+..........
+........F.
+"
+    );
 
-    println!("(9, 9) = {:?}", grid[99]);
+    let mut ppm = Vec::new();
+    let map = RegionMap::new(&points, bounds, &Manhattan);
+    write_ppm(
+        &mut ppm,
+        &points,
+        &map,
+        Some(&SafeRegion::new(&points, 32).unwrap()),
+    )
+    .unwrap();
+
+    assert!(ppm.starts_with(b"P6\n10 10\n255\n"));
+    assert_eq!(ppm.len(), b"P6\n10 10\n255\n".len() + 10 * 10 * 3);
+}
 
-    for y in 0..10 {
-        if y == 0 || y == 9 {
-            for x in 0..10 {
-                println!("{:?}", grid[y * 10 + x].name);
-                if let Some(name) = grid[y * 10 + x].name {
-                    println!("pushing {}", ((name as u8 + b'A') as char));
-                    disqualified.push(name);
-                }
-            }
-        } else {
-            println!("{:?} {:?}", grid[y * 10].name, grid[y * 10 + 9].name);
-            if let Some(name) = grid[y * 10].name {
-                println!("pushing {}", ((name as u8 + b'A') as char));
-                disqualified.push(name);
-            }
+#[test]
+fn smol_test() {
+    let input = "1, 1
+1, 6
+8, 3
+3, 4
+5, 5
+8, 9";
 
-            if let Some(name) = grid[y * 10 + 9].name {
-                println!("pushing {}", ((name as u8 + b'A') as char));
-                disqualified.push(name);
-            }
+    let points = generator(input);
+    let bounds = Bounds {
+        min_x: 0,
+        min_y: 0,
+        max_x: 9,
+        max_y: 9,
+    };
+
+    assert_eq!(
+        largest_finite_area(&points, bounds),
+        AreaReport {
+            areas: vec![15, 14, 21, 9, 17, 13],
+            infinite: vec![true, true, true, false, false, true],
+            winner: Some((4, 17)),
         }
-    }
+    );
 
-    disqualified.dedup();
+    let report = largest_finite_area(&points, Bounds::of(&points).expand(50));
+    assert_eq!(report.winner, Some((4, 17)));
+    assert_eq!(report.areas[3], 9);
+}
 
-    assert_eq!(
-        points
-            .iter()
-            .filter(|p| !disqualified.contains(&p.name.unwrap()))
-            .map(|point| areas[point.name.unwrap() as usize])
-            .max()
-            .unwrap(),
-        17
+#[test]
+fn many_labels_test() {
+    // The real input has 50 sources, more than there are letters.
+    let points = (0..50)
+        .map(|i| Point { x: i * 3, y: 0 })
+        .collect::<Vec<_>>();
+    let bounds = Bounds::of(&points);
+    let map = render(&points, &RegionMap::new(&points, bounds, &Manhattan));
+    let cells = map
+        .trim_end()
+        .as_bytes()
+        .chunks(2)
+        .map(|cell| std::str::from_utf8(cell).unwrap())
+        .collect::<Vec<_>>();
+
+    assert_eq!(cells.len(), bounds.width());
+    assert_eq!(&cells[..4], ["AA", "aa", "ab", "AB"]);
+    assert_eq!(&cells[76..80], ["az", "ba", "BA", "ba"]);
+    assert_eq!(cells[78], label(26, 2).to_ascii_uppercase());
+    assert_ne!(cells[78], cells[0]);
+    assert_eq!(cells[147], "BX");
+
+    assert_eq!(label_width(26), 1);
+    assert_eq!(label_width(27), 2);
+    assert_eq!(label_width(677), 3);
+    assert!(
+        render_safe(&points, &SafeRegion::new(&points, 1).unwrap(), bounds).starts_with("AA....")
     );
 }
+
+#[test]
+fn huge_window_test() {
+    let origin = [Point { x: 0, y: 0 }];
+    let edge = [Point { x: i32::MAX, y: 0 }];
+
+    assert!(SafeRegion::window(&origin, i32::MAX).is_none());
+    assert!(SafeRegion::new(&origin, i32::MAX).is_none());
+    assert!(SafeRegion::window(&edge, 0).is_none());
+    assert!(SafeRegion::window(&origin, i32::MAX / 2).is_none());
+
+    let window = SafeRegion::window(&origin, i32::MAX / 2 - 1).unwrap();
+    assert_eq!(window.max_x, i32::MAX / 2);
+    assert_eq!(window.width(), i32::MAX as usize);
+}
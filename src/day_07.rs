@@ -1,195 +1,575 @@
 use aoc_runner_derive::aoc;
-use binary_heap_plus::BinaryHeap;
-use hashbrown::HashSet;
+use binary_heap_plus::{BinaryHeap, MinComparator};
+use hashbrown::HashMap;
 use petgraph::{
     graphmap::{DiGraphMap, GraphMap},
     prelude::Direction,
 };
+use regex::Regex;
 
-type Name = char;
-type Graph = GraphMap<Name, (), petgraph::prelude::Directed>;
+/// An interned step name. Ids are handed out in lexicographic order of the
+/// names, so ordering ids orders their names.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct StepId(u32);
 
-pub fn generator(input: &str) -> Graph {
-    let mut graph = DiGraphMap::new();
+pub type Graph = GraphMap<StepId, (), petgraph::prelude::Directed>;
 
-    for line in input.lines() {
-        let before = line
-            .split_whitespace()
-            .nth(1)
-            .unwrap()
-            .chars()
-            .next()
-            .unwrap();
+#[derive(Debug, Clone)]
+pub struct Steps {
+    graph: Graph,
+    names: Vec<String>,
+}
 
-        let after = line
-            .split_whitespace()
-            .nth(7)
-            .unwrap()
-            .chars()
-            .next()
-            .unwrap();
+impl std::ops::Deref for Steps {
+    type Target = Graph;
+
+    fn deref(&self) -> &Graph {
+        &self.graph
+    }
+}
 
-        graph.add_edge(before, after, ());
+impl Steps {
+    pub fn name(&self, id: StepId) -> &str {
+        &self.names[id.0 as usize]
     }
 
+    pub fn names(&self, ids: &[StepId]) -> Vec<&str> {
+        ids.iter().map(|id| self.name(*id)).collect()
+    }
+
+    pub fn id(&self, name: &str) -> Option<StepId> {
+        self.names
+            .binary_search_by(|other| other.as_str().cmp(name))
+            .ok()
+            .map(|idx| StepId(idx as u32))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GraphError {
+    Malformed {
+        line: usize,
+        text: String,
+    },
+    /// Some steps can never start. `cycle` is one loop of steps that wait on
+    /// each other, `unreachable` every step stuck behind a loop.
+    Cycle {
+        cycle: Vec<String>,
+        unreachable: Vec<String>,
+    },
+}
+
+impl std::fmt::Display for GraphError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            GraphError::Malformed { line, text } => {
+                write!(f, "malformed instruction on line {}: {:?}", line, text)
+            }
+            GraphError::Cycle { cycle, unreachable } => {
+                write!(f, "steps form a cycle: ")?;
+                for step in cycle {
+                    write!(f, "{} -> ", step)?;
+                }
+                write!(
+                    f,
+                    "{}; unreachable steps: {}",
+                    cycle[0],
+                    unreachable.join(", ")
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for GraphError {}
+
+pub fn generator(input: &str) -> Result<Steps, GraphError> {
+    let regex = Regex::new(r"^Step (\S+) must be finished before step (\S+) can begin\.$").unwrap();
+    let mut edges = Vec::new();
+
+    for (idx, line) in input.lines().enumerate() {
+        let line = line.trim();
+
+        if line.is_empty() {
+            continue;
+        }
+
+        let captures = regex.captures(line).ok_or_else(|| GraphError::Malformed {
+            line: idx + 1,
+            text: line.to_string(),
+        })?;
+
+        edges.push((captures[1].to_string(), captures[2].to_string()));
+    }
+
+    let mut names = edges
+        .iter()
+        .flat_map(|(before, after)| vec![before.clone(), after.clone()])
+        .collect::<Vec<_>>();
+    names.sort_unstable();
+    names.dedup();
+
+    let mut steps = Steps {
+        graph: DiGraphMap::new(),
+        names,
+    };
+
+    for (before, after) in &edges {
+        let (before, after) = (steps.id(before).unwrap(), steps.id(after).unwrap());
+        steps.graph.add_edge(before, after, ());
+    }
+
+    validate(&steps)?;
+
+    Ok(steps)
+}
+
+/// Steps which have to finish before `node` can start. Unlike
+/// `neighbors_directed`, this includes `node` itself when it has a self loop.
+fn predecessors(graph: &Graph, node: StepId) -> impl Iterator<Item = StepId> + '_ {
     graph
+        .neighbors_directed(node, Direction::Incoming)
+        .chain(Some(node).filter(|node| graph.contains_edge(*node, *node)))
+}
+
+/// Kahn's algorithm over every step in the graph, including steps without
+/// any edges. Finishing a step releases the steps waiting on it, and the
+/// lexicographically smallest released step is handed out first.
+pub struct Kahn<'a> {
+    graph: &'a Graph,
+    waiting_on: HashMap<StepId, usize>,
+    ready: BinaryHeap<StepId, MinComparator>,
+}
+
+impl<'a> Kahn<'a> {
+    pub fn new(graph: &'a Graph) -> Kahn<'a> {
+        let waiting_on = graph
+            .nodes()
+            .map(|node| (node, predecessors(graph, node).count()))
+            .collect::<HashMap<_, _>>();
+
+        let mut ready = BinaryHeap::new_min();
+        ready.extend(
+            waiting_on
+                .iter()
+                .filter(|(_, count)| **count == 0)
+                .map(|(node, _)| *node),
+        );
+
+        Kahn {
+            graph,
+            waiting_on,
+            ready,
+        }
+    }
+
+    pub fn pop_ready(&mut self) -> Option<StepId> {
+        self.ready.pop()
+    }
+
+    pub fn finish(&mut self, step: StepId) {
+        for next in self.graph.neighbors(step) {
+            let count = self.waiting_on.get_mut(&next).unwrap();
+            *count -= 1;
+
+            if *count == 0 {
+                self.ready.push(next);
+            }
+        }
+    }
+
+    /// Steps still waiting on something, in no particular order.
+    pub fn waiting(&self) -> impl Iterator<Item = StepId> + '_ {
+        self.waiting_on
+            .iter()
+            .filter(|(_, count)| **count > 0)
+            .map(|(node, _)| *node)
+    }
+}
+
+/// The lexicographically smallest order to do the steps in, or the cycle
+/// that keeps some of them from ever starting.
+pub fn topological_order(steps: &Steps) -> Result<Vec<StepId>, GraphError> {
+    let mut kahn = Kahn::new(steps);
+    let mut order = Vec::with_capacity(steps.node_count());
+
+    while let Some(step) = kahn.pop_ready() {
+        kahn.finish(step);
+        order.push(step);
+    }
+
+    if order.len() < steps.node_count() {
+        return Err(cycle_error(steps, kahn.waiting().collect()));
+    }
+
+    Ok(order)
+}
+
+/// Every order the steps can be done in, in lexicographic order. The count
+/// grows factorially with the number of independent steps, so this is only
+/// meant for small graphs.
+pub fn all_orders(steps: &Steps) -> Result<Vec<Vec<StepId>>, GraphError> {
+    fn extend(
+        graph: &Graph,
+        waiting_on: &mut HashMap<StepId, usize>,
+        order: &mut Vec<StepId>,
+        orders: &mut Vec<Vec<StepId>>,
+    ) {
+        let mut ready = waiting_on
+            .iter()
+            .filter(|(_, count)| **count == 0)
+            .map(|(node, _)| *node)
+            .collect::<Vec<_>>();
+        ready.sort_unstable();
+
+        if ready.is_empty() {
+            if waiting_on.is_empty() {
+                orders.push(order.clone());
+            }
+
+            return;
+        }
+
+        for step in ready {
+            waiting_on.remove(&step);
+            graph.neighbors(step).for_each(|next| {
+                *waiting_on.get_mut(&next).unwrap() -= 1;
+            });
+            order.push(step);
+
+            extend(graph, waiting_on, order, orders);
+
+            order.pop();
+            graph.neighbors(step).for_each(|next| {
+                *waiting_on.get_mut(&next).unwrap() += 1;
+            });
+            waiting_on.insert(step, 0);
+        }
+    }
+
+    validate(steps)?;
+
+    let mut waiting_on = steps
+        .nodes()
+        .map(|node| (node, predecessors(steps, node).count()))
+        .collect::<HashMap<_, _>>();
+    let mut orders = Vec::new();
+
+    extend(steps, &mut waiting_on, &mut Vec::new(), &mut orders);
+
+    Ok(orders)
+}
+
+pub fn validate(steps: &Steps) -> Result<(), GraphError> {
+    topological_order(steps).map(|_| ())
+}
+
+/// Anything still waiting once no step is left to start sits on or behind a
+/// cycle.
+fn cycle_error(graph: &Steps, mut unreachable: Vec<StepId>) -> GraphError {
+    unreachable.sort_unstable();
+
+    // Every stuck step waits on another stuck step, so walking backwards
+    // through them has to come round to a step already seen.
+    let mut path = vec![unreachable[0]];
+    loop {
+        let current = *path.last().unwrap();
+        let prev = predecessors(graph, current)
+            .filter(|node| unreachable.binary_search(node).is_ok())
+            .min()
+            .unwrap();
+
+        if let Some(start) = path.iter().position(|node| *node == prev) {
+            let mut cycle = path.split_off(start);
+            cycle.reverse();
+            cycle.rotate_right(1);
+
+            let to_names =
+                |ids: &[StepId]| ids.iter().map(|id| graph.name(*id).to_string()).collect();
+
+            return GraphError::Cycle {
+                cycle: to_names(&cycle),
+                unreachable: to_names(&unreachable),
+            };
+        }
+
+        path.push(prev);
+    }
 }
 
 #[aoc(day7, part1)]
 pub fn day_7_part_1(input: &str) -> String {
-    let mut steps = String::new();
-    let graph = generator(input);
-    let mut processed = HashSet::new();
+    let graph = generator(input).unwrap();
 
-    let mut start_nodes = graph
-        .all_edges()
-        .filter(|(before, _, _)| {
-            graph
-                .neighbors_directed(*before, Direction::Incoming)
-                .next()
-                .is_none()
-        })
-        .map(|(before, _, _)| before)
-        .collect::<Vec<_>>();
+    topological_order(&graph)
+        .unwrap()
+        .into_iter()
+        .map(|step| graph.name(step))
+        .collect()
+}
 
-    start_nodes.sort_unstable();
-    start_nodes.dedup();
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Task {
+    pub worker: usize,
+    pub step: StepId,
+    pub start: u32,
+    pub end: u32,
+}
 
-    let mut queue = BinaryHeap::new_min();
-    queue.extend(start_nodes.into_iter());
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Schedule {
+    pub workers: usize,
+    pub total_time: u32,
+    /// Every step in the order it was started, ties broken by worker.
+    pub timeline: Vec<Task>,
+}
 
-    while !queue.is_empty() {
-        let root = queue.pop().unwrap();
+const SVG_SECOND: u32 = 10;
+const SVG_ROW: u32 = 24;
 
-        if !processed.contains(&root) {
-            steps.push(root);
-            processed.insert(root);
+impl Schedule {
+    /// The per-second view from the puzzle, one row per second showing what
+    /// each worker is busy with and every step done so far.
+    pub fn render_table(&self, steps: &Steps) -> String {
+        use std::fmt::Write;
 
-            queue.extend(graph.neighbors(root).filter(|node| {
-                graph
-                    .neighbors_directed(*node, Direction::Incoming)
-                    .all(|node| processed.contains(&node))
-            }));
+        let mut out = String::from("Second");
+        for worker in 1..=self.workers {
+            write!(out, "   Worker {}", worker).unwrap();
         }
+        out.push_str("   Done\n");
+
+        let mut finished = self.timeline.clone();
+        finished.sort_by_key(|task| (task.end, task.step));
+
+        for second in 0..=self.total_time {
+            let mut row = format!("{:>4}", second);
+
+            for worker in 0..self.workers {
+                let step = self
+                    .timeline
+                    .iter()
+                    .find(|task| task.worker == worker && task.start <= second && second < task.end)
+                    .map_or(".", |task| steps.name(task.step));
+
+                let width = if worker == 0 { 9 } else { 11 };
+                write!(row, "{:>width$}", step, width = width).unwrap();
+            }
+
+            row.push_str("       ");
+            row.extend(
+                finished
+                    .iter()
+                    .take_while(|task| task.end <= second)
+                    .map(|task| steps.name(task.step)),
+            );
+
+            out.push_str(row.trim_end());
+            out.push('\n');
+        }
+
+        out
     }
 
-    steps
+    /// A Gantt chart of the schedule with a row per worker.
+    pub fn render_svg(&self, steps: &Steps) -> String {
+        use std::fmt::Write;
+
+        let mut out = String::new();
+        writeln!(
+            out,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}">"#,
+            self.total_time * SVG_SECOND,
+            self.workers as u32 * SVG_ROW
+        )
+        .unwrap();
+
+        for task in &self.timeline {
+            let (x, y) = (task.start * SVG_SECOND, task.worker as u32 * SVG_ROW);
+
+            writeln!(
+                out,
+                r#"  <rect x="{}" y="{}" width="{}" height="{}" fill="steelblue" stroke="white"/>"#,
+                x,
+                y,
+                (task.end - task.start) * SVG_SECOND,
+                SVG_ROW
+            )
+            .unwrap();
+            writeln!(
+                out,
+                r#"  <text x="{}" y="{}" font-family="monospace" fill="white">{}</text>"#,
+                x + 2,
+                y + SVG_ROW * 2 / 3,
+                escape_xml(steps.name(task.step))
+            )
+            .unwrap();
+        }
+
+        out.push_str("</svg>\n");
+        out
+    }
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
 }
 
-#[derive(Default, Clone, Copy)]
-pub struct Worker {
-    start_time: u32,
-    current_time: u32,
-    processing: Option<Name>,
+pub struct Scheduler<F> {
+    workers: usize,
+    duration: F,
 }
 
-impl Worker {
-    pub fn give_work(&mut self, start_time: u32, n: Name) {
-        self.start_time = start_time;
-        self.current_time = start_time;
-        self.processing = Some(n);
+impl<F: Fn(&str) -> u32> Scheduler<F> {
+    /// Panics if there are no workers, as no step would ever get done.
+    pub fn new(workers: usize, duration: F) -> Scheduler<F> {
+        assert!(workers > 0, "a schedule needs at least one worker");
+
+        Scheduler { workers, duration }
     }
 
-    pub fn work(&mut self) -> Option<Name> {
-        self.current_time += 1;
+    pub fn run(&self, graph: &Steps) -> Schedule {
+        let mut kahn = Kahn::new(graph);
+        let mut busy: Vec<Option<Task>> = vec![None; self.workers];
+        let mut timeline = Vec::with_capacity(graph.node_count());
+        let mut time = 0;
+
+        loop {
+            for (worker, slot) in busy.iter_mut().enumerate() {
+                if slot.is_none() {
+                    if let Some(step) = kahn.pop_ready() {
+                        let task = Task {
+                            worker,
+                            step,
+                            start: time,
+                            end: time + (self.duration)(graph.name(step)),
+                        };
+
+                        timeline.push(task);
+                        *slot = Some(task);
+                    }
+                }
+            }
 
-        if let Some(work) = self.processing {
-            #[cfg(not(test))]
-            let time = u32::from(work as u8 - 4);
-            #[cfg(test)]
-            let time = u32::from(work as u8 - 64);
+            time = match busy.iter().flatten().map(|task| task.end).min() {
+                Some(end) => end,
+                None => break,
+            };
 
-            if self.current_time == self.start_time + time {
-                let ret = self.processing;
-                self.processing = None;
-                ret
-            } else {
-                None
+            for slot in busy.iter_mut() {
+                if let Some(task) = slot.filter(|task| task.end == time) {
+                    *slot = None;
+                    kahn.finish(task.step);
+                }
             }
-        } else {
-            None
+        }
+
+        Schedule {
+            workers: self.workers,
+            total_time: time,
+            timeline,
         }
     }
+}
 
-    pub fn has_work(&self) -> bool {
-        self.processing.is_some()
+/// The puzzle's step duration: a fixed cost plus the place in the alphabet of
+/// every letter in the step's name.
+pub fn step_duration(base: u32) -> impl Fn(&str) -> u32 {
+    move |step| {
+        base + step
+            .bytes()
+            .filter(u8::is_ascii_alphabetic)
+            .map(|c| u32::from(c.to_ascii_uppercase() - b'A' + 1))
+            .sum::<u32>()
     }
 }
 
 #[aoc(day7, part2)]
 pub fn day_7_part_2(input: &str) -> u32 {
-    let graph = generator(input);
-    let mut processed = HashSet::new();
+    Scheduler::new(5, step_duration(60))
+        .run(&generator(input).unwrap())
+        .total_time
+}
 
-    let mut start_nodes = graph
+pub fn to_dot(graph: &Steps) -> String {
+    use std::fmt::Write;
+
+    let mut nodes = graph.nodes().collect::<Vec<_>>();
+    nodes.sort_unstable();
+
+    let mut edges = graph
         .all_edges()
-        .filter(|(before, _, _)| {
-            graph
-                .neighbors_directed(*before, Direction::Incoming)
-                .next()
-                .is_none()
-        })
-        .map(|(before, _, _)| before)
+        .map(|(a, b, _)| (a, b))
         .collect::<Vec<_>>();
+    edges.sort_unstable();
 
-    start_nodes.sort_unstable();
-    start_nodes.dedup();
+    let quote = |id| {
+        let name = graph.name(id).replace('\\', "\\\\").replace('"', "\\\"");
+        format!("\"{}\"", name)
+    };
 
-    let mut queue = BinaryHeap::new_min();
-    queue.extend(start_nodes.into_iter());
+    let mut out = String::from("digraph steps {\n");
+    for node in nodes {
+        writeln!(out, "    {};", quote(node)).unwrap();
+    }
+    for (before, after) in edges {
+        writeln!(out, "    {} -> {};", quote(before), quote(after)).unwrap();
+    }
+    out.push_str("}\n");
 
-    #[cfg(not(test))]
-    let mut workers = [Worker::default(); 5];
-    #[cfg(test)]
-    let mut workers = [Worker::default(); 2];
-    let mut time = 0;
+    out
+}
 
-    for worker in workers.iter_mut() {
-        if let Some(node) = queue.pop() {
-            worker.give_work(time, node);
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CriticalPath {
+    pub length: u32,
+    pub steps: Vec<StepId>,
+}
+
+/// The chain of steps with the longest total duration, which bounds how
+/// quickly any number of workers can finish.
+pub fn critical_path(
+    graph: &Steps,
+    duration: impl Fn(&str) -> u32,
+) -> Result<CriticalPath, GraphError> {
+    let order = topological_order(graph)?;
+    let mut finish = HashMap::new();
+    let mut via = HashMap::new();
+
+    for &step in &order {
+        let before = predecessors(graph, step)
+            .map(|prev| (finish[&prev], std::cmp::Reverse(prev)))
+            .max();
+
+        let start = before.map_or(0, |(end, _)| end);
+        finish.insert(step, start + duration(graph.name(step)));
+
+        if let Some((_, std::cmp::Reverse(prev))) = before {
+            via.insert(step, prev);
         }
     }
 
-    while workers.iter().any(|worker| worker.has_work()) || !queue.is_empty() {
-        /*#[cfg(not(test))]
-        {
-            println!(
-                "Time: {:<10}{}{:>10}{:>10}{:>10}{:>10}",
-                time,
-                workers[0].processing.unwrap_or('.'),
-                workers[1].processing.unwrap_or('.'),
-                workers[2].processing.unwrap_or('.'),
-                workers[3].processing.unwrap_or('.'),
-                workers[4].processing.unwrap_or('.'),
-            );
-        }*/
-
-        for worker in workers.iter_mut() {
-            if let Some(root) = worker.work() {
-                if !processed.contains(&root) {
-                    processed.insert(root);
-
-                    queue.extend(graph.neighbors(root).filter(|node| {
-                        graph
-                            .neighbors_directed(*node, Direction::Incoming)
-                            .all(|node| processed.contains(&node))
-                    }));
-                }
-            }
-        }
+    let last = order
+        .iter()
+        .map(|step| (finish[step], std::cmp::Reverse(*step)))
+        .max();
 
-        for worker in workers.iter_mut() {
-            if !worker.has_work() {
-                if let Some(node) = queue.pop() {
-                    worker.give_work(time, node);
-                }
-            }
-        }
+    let mut steps = Vec::new();
+    let mut current = last.map(|(_, std::cmp::Reverse(step))| step);
 
-        time += 1;
+    while let Some(step) = current {
+        steps.push(step);
+        current = via.get(&step).cloned();
     }
+    steps.reverse();
 
-    time
+    Ok(CriticalPath {
+        length: last.map_or(0, |(length, _)| length),
+        steps,
+    })
 }
 
 #[test]
@@ -218,6 +598,12 @@ Step F must be finished before step E can begin.";
     assert_eq!(day_7_part_1(input), "CABDFE");
 }
 
+#[test]
+#[should_panic(expected = "at least one worker")]
+fn no_workers_test() {
+    Scheduler::new(0, step_duration(0));
+}
+
 #[test]
 fn smol_test_7_part_2() {
     let input = "Step C must be finished before step A can begin.
@@ -228,5 +614,251 @@ Step B must be finished before step E can begin.
 Step D must be finished before step E can begin.
 Step F must be finished before step E can begin.";
 
-    assert_eq!(day_7_part_2(input), 15);
+    let steps = generator(input).unwrap();
+    let schedule = Scheduler::new(2, step_duration(0)).run(&steps);
+
+    assert_eq!(schedule.total_time, 15);
+    assert_eq!(
+        schedule
+            .timeline
+            .iter()
+            .map(|task| (task.worker, steps.name(task.step), task.start, task.end))
+            .collect::<Vec<_>>(),
+        vec![
+            (0, "C", 0, 3),
+            (0, "A", 3, 4),
+            (1, "F", 3, 9),
+            (0, "B", 4, 6),
+            (0, "D", 6, 10),
+            (0, "E", 10, 15),
+        ]
+    );
+
+    assert_eq!(day_7_part_2(input), 253);
+}
+
+#[test]
+fn render_schedule_test() {
+    let input = "Step C must be finished before step A can begin.
+Step C must be finished before step F can begin.
+Step A must be finished before step B can begin.
+Step A must be finished before step D can begin.
+Step B must be finished before step E can begin.
+Step D must be finished before step E can begin.
+Step F must be finished before step E can begin.";
+
+    let steps = generator(input).unwrap();
+    let schedule = Scheduler::new(2, step_duration(0)).run(&steps);
+
+    assert_eq!(
+        schedule.render_table(&steps),
+        "Second   Worker 1   Worker 2   Done
+   0        C          .
+   1        C          .
+   2        C          .
+   3        A          F       C
+   4        B          F       CA
+   5        B          F       CA
+   6        D          F       CAB
+   7        D          F       CAB
+   8        D          F       CAB
+   9        D          .       CABF
+  10        E          .       CABFD
+  11        E          .       CABFD
+  12        E          .       CABFD
+  13        E          .       CABFD
+  14        E          .       CABFD
+  15        .          .       CABFDE
+"
+    );
+
+    let svg = schedule.render_svg(&steps);
+    assert!(svg.starts_with(r#"<svg xmlns="http://www.w3.org/2000/svg" width="150" height="48">"#));
+    assert_eq!(svg.matches("<rect").count(), 6);
+    assert!(svg.contains(r#"<rect x="30" y="24" width="60" height="24""#));
+
+    let steps = generator(r#"Step <a&b> must be finished before step "c" can begin."#).unwrap();
+    let svg = Scheduler::new(1, step_duration(0))
+        .run(&steps)
+        .render_svg(&steps);
+    assert!(svg.contains(">&lt;a&amp;b&gt;</text>"));
+    assert!(svg.contains(">&quot;c&quot;</text>"));
+}
+
+#[test]
+fn validate_test() {
+    let input = "Step C must be finished before step A can begin.
+Step A must be finished before step B can begin.
+Step B must be finished before step D can begin.
+Step D must be finished before step A can begin.
+Step D must be finished before step E can begin.
+Step C must be finished before step F can begin.";
+
+    let error = generator(input).unwrap_err();
+    assert_eq!(
+        error,
+        GraphError::Cycle {
+            cycle: vec!["A".to_string(), "B".to_string(), "D".to_string()],
+            unreachable: vec![
+                "A".to_string(),
+                "B".to_string(),
+                "D".to_string(),
+                "E".to_string()
+            ],
+        }
+    );
+    assert_eq!(
+        error.to_string(),
+        "steps form a cycle: A -> B -> D -> A; unreachable steps: A, B, D, E"
+    );
+
+    assert_eq!(
+        generator("Step A must be finished before step A can begin.").unwrap_err(),
+        GraphError::Cycle {
+            cycle: vec!["A".to_string()],
+            unreachable: vec!["A".to_string()],
+        }
+    );
+
+    assert_eq!(
+        generator(
+            "Step A must be finished before step B can begin.
+Step B must be done before step C can begin."
+        )
+        .unwrap_err(),
+        GraphError::Malformed {
+            line: 2,
+            text: "Step B must be done before step C can begin.".to_string(),
+        }
+    );
+}
+
+#[test]
+fn critical_path_test() {
+    let input = "Step C must be finished before step A can begin.
+Step C must be finished before step F can begin.
+Step A must be finished before step B can begin.
+Step A must be finished before step D can begin.
+Step B must be finished before step E can begin.
+Step D must be finished before step E can begin.
+Step F must be finished before step E can begin.";
+
+    let graph = generator(input).unwrap();
+
+    let path = critical_path(&graph, step_duration(0)).unwrap();
+    assert_eq!(path.length, 14);
+    assert_eq!(graph.names(&path.steps), ["C", "F", "E"]);
+
+    let path = critical_path(&graph, step_duration(60)).unwrap();
+    assert_eq!(path.length, 253);
+    assert_eq!(graph.names(&path.steps), ["C", "A", "D", "E"]);
+
+    let mut cyclic = graph.clone();
+    cyclic
+        .graph
+        .add_edge(graph.id("E").unwrap(), graph.id("C").unwrap(), ());
+    assert_eq!(
+        critical_path(&cyclic, step_duration(0)).unwrap_err(),
+        topological_order(&cyclic).unwrap_err()
+    );
+    assert_eq!(
+        Scheduler::new(graph.node_count(), step_duration(0))
+            .run(&graph)
+            .total_time,
+        14
+    );
+
+    assert_eq!(
+        to_dot(&generator("Step B must be finished before step A can begin.").unwrap()),
+        "digraph steps {
+    \"A\";
+    \"B\";
+    \"B\" -> \"A\";
+}
+"
+    );
+
+    let steps = generator(r#"Step a\ must be finished before step "b" can begin."#).unwrap();
+    assert!(to_dot(&steps).contains(r#"    "a\\" -> "\"b\"";"#));
+}
+
+#[test]
+fn multi_character_names_test() {
+    let input = "Step build must be finished before step test can begin.
+Step fetch must be finished before step build can begin.
+Step fetch must be finished before step lint can begin.
+Step lint must be finished before step test can begin.
+Step test must be finished before step deploy can begin.
+Step 10 must be finished before step 9 can begin.";
+
+    let steps = generator(input).unwrap();
+
+    assert_eq!(steps.id("fetch").map(|id| steps.name(id)), Some("fetch"));
+    assert_eq!(steps.id("fet"), None);
+    assert_eq!(
+        day_7_part_1(input),
+        ["10", "9", "fetch", "build", "lint", "test", "deploy"].concat()
+    );
+
+    let schedule = Scheduler::new(2, |name: &str| name.len() as u32).run(&steps);
+    assert_eq!(schedule.total_time, 5 + 5 + 4 + 6);
+    assert_eq!(step_duration(0)("AB"), 3);
+    assert_eq!(step_duration(0)("10"), 0);
+}
+
+#[test]
+fn topological_order_test() {
+    let mut graph = Graph::new();
+    let (a, b, c, d) = (StepId(0), StepId(1), StepId(2), StepId(3));
+
+    graph.add_edge(c, a, ());
+    graph.add_edge(c, b, ());
+    graph.add_node(d);
+
+    let mut steps = Steps {
+        graph,
+        names: vec!["a".into(), "b".into(), "c".into(), "d".into()],
+    };
+
+    assert_eq!(topological_order(&steps).unwrap(), [c, a, b, d]);
+    assert_eq!(
+        all_orders(&steps).unwrap(),
+        vec![
+            vec![c, a, b, d],
+            vec![c, a, d, b],
+            vec![c, b, a, d],
+            vec![c, b, d, a],
+            vec![c, d, a, b],
+            vec![c, d, b, a],
+            vec![d, c, a, b],
+            vec![d, c, b, a],
+        ]
+    );
+
+    steps.graph.add_edge(a, c, ());
+    let cycle = GraphError::Cycle {
+        cycle: vec!["a".into(), "c".into()],
+        unreachable: vec!["a".into(), "b".into(), "c".into()],
+    };
+    assert_eq!(topological_order(&steps).unwrap_err(), cycle);
+    assert_eq!(all_orders(&steps).unwrap_err(), cycle);
+
+    steps.graph = Graph::new();
+    steps.names.clear();
+    assert_eq!(topological_order(&steps).unwrap(), []);
+    assert_eq!(all_orders(&steps).unwrap(), [[]]);
+
+    let input = "Step C must be finished before step A can begin.
+Step C must be finished before step F can begin.
+Step A must be finished before step B can begin.
+Step A must be finished before step D can begin.
+Step B must be finished before step E can begin.
+Step D must be finished before step E can begin.
+Step F must be finished before step E can begin.";
+
+    let steps = generator(input).unwrap();
+    let orders = all_orders(&steps).unwrap();
+
+    assert_eq!(orders.len(), 8);
+    assert_eq!(orders[0], topological_order(&steps).unwrap());
 }
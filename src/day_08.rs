@@ -1,79 +1,635 @@
-use aoc_runner_derive::aoc;
+use aoc_runner_derive::{aoc, aoc_generator};
 
-#[aoc(day8, part1)]
-pub fn day_8_part_1(input: &str) -> u32 {
-    let mut iter = input.split_whitespace().map(|s| s.parse::<u8>().unwrap());
-    helper_part_1(&mut iter)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Node {
+    pub children: Vec<Node>,
+    pub metadata: Vec<u32>,
 }
 
-pub fn helper_part_1(input: &mut impl Iterator<Item = u8>) -> u32 {
-    let mut sum = 0;
-    let children = if let Some(input) = input.next() {
-        input
-    } else {
-        return 0;
-    };
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    /// The input ended while `expected` was still to be read.
+    Truncated {
+        expected: &'static str,
+    },
+    InvalidNumber {
+        position: usize,
+        token: String,
+    },
+    /// Numbers were left over after the root node ended.
+    TrailingInput {
+        position: usize,
+    },
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ParseError::Truncated { expected } => {
+                write!(f, "input ended while reading {}", expected)
+            }
+            ParseError::InvalidNumber { position, token } => {
+                write!(f, "invalid number {:?} at position {}", token, position)
+            }
+            ParseError::TrailingInput { position } => {
+                write!(
+                    f,
+                    "unexpected input after the root node at position {}",
+                    position
+                )
+            }
+        }
+    }
+}
 
-    let metadata = input.next().unwrap();
+impl std::error::Error for ParseError {}
 
-    for _ in 0..children {
-        sum += helper_part_1(input);
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValueError {
+    NoSuchNode {
+        path: String,
+    },
+    /// The value doesn't fit in a `u64`. Values can double with every level,
+    /// as metadata may point at the same child more than once.
+    Overflow,
+}
+
+impl std::fmt::Display for ValueError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ValueError::NoSuchNode { path } => write!(f, "no node at path {:?}", path),
+            ValueError::Overflow => write!(f, "node value doesn't fit in a u64"),
+        }
     }
+}
+
+impl std::error::Error for ValueError {}
 
-    for _ in 0..metadata {
-        sum += u32::from(input.next().unwrap());
+// Lets aoc-runner hand the generated tree to the parts by reference.
+impl AsRef<Node> for Node {
+    fn as_ref(&self) -> &Node {
+        self
     }
+}
+
+impl std::fmt::Display for Node {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for (idx, number) in self.to_numbers().into_iter().enumerate() {
+            if idx > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{}", number)?;
+        }
 
-    sum
+        Ok(())
+    }
 }
 
-#[aoc(day8, part2)]
-pub fn day_8_part_2(input: &str) -> u32 {
-    let mut iter = input.split_whitespace().map(|s| s.parse::<u8>().unwrap());
-    helper_part_2(&mut iter)
+// Dropping a deep tree recursively would overflow the stack just like a
+// recursive parser, so children are unlinked one level at a time instead.
+impl Drop for Node {
+    fn drop(&mut self) {
+        let mut stack = std::mem::take(&mut self.children);
+
+        while let Some(mut node) = stack.pop() {
+            stack.append(&mut node.children);
+        }
+    }
 }
 
-pub fn helper_part_2(input: &mut impl Iterator<Item = u8>) -> u32 {
-    let mut sum = 0;
-    let children = if let Some(input) = input.next() {
-        input
-    } else {
-        return 0;
-    };
+pub trait Visitor {
+    fn visit(&mut self, node: &Node, depth: usize);
+}
+
+impl<F: FnMut(&Node, usize)> Visitor for F {
+    fn visit(&mut self, node: &Node, depth: usize) {
+        self(node, depth)
+    }
+}
+
+struct Numbers<'a> {
+    tokens: std::iter::Enumerate<std::str::SplitWhitespace<'a>>,
+}
+
+impl<'a> Numbers<'a> {
+    fn next(&mut self, expected: &'static str) -> Result<u32, ParseError> {
+        let (position, token) = self
+            .tokens
+            .next()
+            .ok_or(ParseError::Truncated { expected })?;
+
+        token.parse().map_err(|_| ParseError::InvalidNumber {
+            position,
+            token: token.to_string(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TreeShape {
+    pub max_depth: usize,
+    pub max_nodes: usize,
+    pub max_children: u32,
+    pub max_metadata: u32,
+    /// Metadata entries are drawn from `1..=max_value`.
+    pub max_value: u32,
+}
+
+struct XorShift(u64);
+
+impl XorShift {
+    fn below(&mut self, bound: u32) -> u32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+
+        (self.0 % u64::from(bound.max(1))) as u32
+    }
+}
+
+/// The tree laid out breadth first, so every node's children sit next to
+/// each other and after it.
+struct Layout<'a> {
+    nodes: Vec<&'a Node>,
+    first_child: Vec<usize>,
+}
+
+impl<'a> Layout<'a> {
+    fn new(root: &'a Node) -> Layout<'a> {
+        let mut nodes = vec![root];
+        let mut first_child = Vec::new();
+        let mut idx = 0;
+        while let Some(node) = nodes.get(idx) {
+            first_child.push(nodes.len());
+            nodes.extend(node.children.iter());
+            idx += 1;
+        }
+
+        Layout { nodes, first_child }
+    }
+
+    /// Every node's value, worked out from the back so children come first.
+    /// `None` marks a value too large for a `u64`, and spreads to the nodes
+    /// counting it.
+    fn values(&self) -> Vec<Option<u64>> {
+        let mut values = vec![Some(0); self.nodes.len()];
+
+        for (idx, node) in self.nodes.iter().enumerate().rev() {
+            values[idx] = if node.children.is_empty() {
+                node.metadata
+                    .iter()
+                    .try_fold(0u64, |sum, m| sum.checked_add(u64::from(*m)))
+            } else {
+                node.metadata
+                    .iter()
+                    .filter_map(|m| (*m as usize).checked_sub(1))
+                    .filter(|child| *child < node.children.len())
+                    .try_fold(0u64, |sum, child| {
+                        sum.checked_add(values[self.first_child[idx] + child]?)
+                    })
+            };
+        }
+
+        values
+    }
+}
+
+/// `A` to `Z`, then `AA`, `AB` and so on.
+fn label(mut idx: usize) -> String {
+    let mut label = Vec::new();
+
+    loop {
+        label.push(b'A' + (idx % 26) as u8);
+
+        if idx < 26 {
+            break;
+        }
+        idx = idx / 26 - 1;
+    }
+
+    label.reverse();
+    String::from_utf8(label).unwrap()
+}
+
+struct Frame {
+    children_left: u32,
+    metadata: u32,
+    children: Vec<Node>,
+}
+
+impl Node {
+    /// Parses the flat license format without recursing, keeping the nodes
+    /// still waiting on children on an explicit stack.
+    pub fn parse(input: &str) -> Result<Node, ParseError> {
+        let mut numbers = Numbers {
+            tokens: input.split_whitespace().enumerate(),
+        };
+        let mut stack = Vec::new();
+
+        let header = |numbers: &mut Numbers| -> Result<Frame, ParseError> {
+            Ok(Frame {
+                children_left: numbers.next("a child count")?,
+                metadata: numbers.next("a metadata count")?,
+                children: Vec::new(),
+            })
+        };
+
+        stack.push(header(&mut numbers)?);
+
+        let root = loop {
+            let frame = stack.last_mut().unwrap();
+
+            if frame.children_left > 0 {
+                frame.children_left -= 1;
+                let child = header(&mut numbers)?;
+                stack.push(child);
+                continue;
+            }
+
+            let frame = stack.pop().unwrap();
+            let node = Node {
+                metadata: (0..frame.metadata)
+                    .map(|_| numbers.next("metadata"))
+                    .collect::<Result<_, _>>()?,
+                children: frame.children,
+            };
+
+            match stack.last_mut() {
+                Some(parent) => parent.children.push(node),
+                None => break node,
+            }
+        };
+
+        match numbers.tokens.next() {
+            Some((position, _)) => Err(ParseError::TrailingInput { position }),
+            None => Ok(root),
+        }
+    }
+
+    /// Flattens the tree back into the format `parse` reads.
+    pub fn to_numbers(&self) -> Vec<u32> {
+        enum Step<'a> {
+            Enter(&'a Node),
+            Metadata(&'a Node),
+        }
+
+        let mut numbers = Vec::new();
+        let mut stack = vec![Step::Enter(self)];
+
+        while let Some(step) = stack.pop() {
+            match step {
+                Step::Enter(node) => {
+                    numbers.push(node.children.len() as u32);
+                    numbers.push(node.metadata.len() as u32);
+
+                    stack.push(Step::Metadata(node));
+                    stack.extend(node.children.iter().rev().map(Step::Enter));
+                }
+                Step::Metadata(node) => numbers.extend_from_slice(&node.metadata),
+            }
+        }
 
-    let metadata = input.next().unwrap();
-    let mut child_sum = Vec::with_capacity(children as usize);
+        numbers
+    }
+
+    /// Builds a random tree of at most `shape.max_nodes` nodes, the same one
+    /// for a given `seed`.
+    pub fn random(shape: &TreeShape, seed: u64) -> Node {
+        let mut rng = XorShift(seed.max(1));
+        let mut nodes = 1;
+        let mut stack = Vec::new();
+
+        let mut header = |depth: usize, rng: &mut XorShift| {
+            let children = if depth + 1 < shape.max_depth {
+                let wanted = rng.below(shape.max_children + 1);
+                let allowed = shape.max_nodes.saturating_sub(nodes) as u32;
+                wanted.min(allowed)
+            } else {
+                0
+            };
+            nodes += children as usize;
+
+            Frame {
+                children_left: children,
+                metadata: rng.below(shape.max_metadata + 1),
+                children: Vec::with_capacity(children as usize),
+            }
+        };
+
+        stack.push(header(0, &mut rng));
 
-    for _ in 0..children {
-        child_sum.push(helper_part_2(input));
+        loop {
+            let depth = stack.len();
+            let frame = stack.last_mut().unwrap();
+
+            if frame.children_left > 0 {
+                frame.children_left -= 1;
+                let child = header(depth, &mut rng);
+                stack.push(child);
+                continue;
+            }
+
+            let frame = stack.pop().unwrap();
+            let node = Node {
+                metadata: (0..frame.metadata)
+                    .map(|_| 1 + rng.below(shape.max_value))
+                    .collect(),
+                children: frame.children,
+            };
+
+            match stack.last_mut() {
+                Some(parent) => parent.children.push(node),
+                None => return node,
+            }
+        }
     }
 
-    if children == 0 {
-        for _ in 0..metadata {
-            sum += u32::from(input.next().unwrap());
+    pub fn metadata_sum(&self) -> u64 {
+        let mut sum = 0;
+        self.walk(&mut |node: &Node, _| {
+            sum += node.metadata.iter().map(|m| u64::from(*m)).sum::<u64>()
+        });
+
+        sum
+    }
+
+    /// A leaf is worth its metadata. Any other node is worth the children its
+    /// metadata points at, 1-indexed, with entries pointing at no child worth
+    /// nothing. The only error is `ValueError::Overflow`.
+    pub fn value(&self) -> Result<u64, ValueError> {
+        Layout::new(self).values()[0].ok_or(ValueError::Overflow)
+    }
+
+    /// Finds a node by the 0-based child indices leading to it from this node,
+    /// separated by `/`. The empty path is this node.
+    pub fn at(&self, path: &str) -> Option<&Node> {
+        path.split('/')
+            .filter(|segment| !segment.is_empty())
+            .try_fold(self, |node, segment| {
+                node.children.get(segment.parse::<usize>().ok()?)
+            })
+    }
+
+    pub fn value_at(&self, path: &str) -> Result<u64, ValueError> {
+        self.at(path)
+            .ok_or_else(|| ValueError::NoSuchNode {
+                path: path.to_string(),
+            })?
+            .value()
+    }
+
+    /// An indented outline of the tree, one node per line, with nodes
+    /// lettered in the order they appear in the input like the puzzle does.
+    pub fn outline(&self) -> String {
+        use std::fmt::Write;
+
+        let layout = Layout::new(self);
+        let values = layout.values();
+        let mut out = String::new();
+        let mut stack = vec![(0, 0)];
+        let mut count = 0;
+
+        while let Some((idx, depth)) = stack.pop() {
+            let node = layout.nodes[idx];
+
+            writeln!(
+                out,
+                "{:indent$}{}: {} {}, metadata {:?}, value {}",
+                "",
+                label(count),
+                node.children.len(),
+                if node.children.len() == 1 {
+                    "child"
+                } else {
+                    "children"
+                },
+                node.metadata,
+                values[idx].map_or("too large for a u64".to_string(), |v| v.to_string()),
+                indent = depth * 2
+            )
+            .unwrap();
+            count += 1;
+
+            let first = layout.first_child[idx];
+            stack.extend(
+                (first..first + node.children.len())
+                    .rev()
+                    .map(|child| (child, depth + 1)),
+            );
         }
-    } else {
-        for _ in 0..metadata {
-            sum += child_sum
-                .get(input.next().unwrap() as usize - 1)
-                .cloned()
-                .unwrap_or(0);
+
+        out
+    }
+
+    /// Levels in the tree, counting this node as the first.
+    pub fn depth(&self) -> usize {
+        let mut deepest = 0;
+        self.walk(&mut |_: &Node, depth: usize| deepest = deepest.max(depth));
+
+        deepest + 1
+    }
+
+    pub fn node_count(&self) -> usize {
+        let mut count = 0;
+        self.walk(&mut |_: &Node, _| count += 1);
+
+        count
+    }
+
+    /// Visits every node depth first, parents before their children, with
+    /// this node at depth 0.
+    pub fn walk(&self, visitor: &mut impl Visitor) {
+        let mut stack = vec![(self, 0)];
+
+        while let Some((node, depth)) = stack.pop() {
+            visitor.visit(node, depth);
+            stack.extend(node.children.iter().rev().map(|child| (child, depth + 1)));
         }
     }
+}
 
-    sum
+#[aoc_generator(day8)]
+pub fn generator(input: &str) -> Node {
+    Node::parse(input).unwrap()
+}
+
+#[aoc(day8, part1)]
+pub fn day_8_part_1(input: &Node) -> u64 {
+    input.metadata_sum()
+}
+
+#[aoc(day8, part2)]
+pub fn day_8_part_2(input: &Node) -> u64 {
+    input.value().unwrap()
 }
 
 #[test]
 fn smol_test_8() {
     let input = "2 3 0 3 10 11 12 1 1 0 1 99 2 1 1 2";
 
-    assert_eq!(day_8_part_1(input), 138);
+    assert_eq!(day_8_part_1(&generator(input)), 138);
 }
 
 #[test]
 fn smol_test_8_part_2() {
     let input = "2 3 0 3 10 11 12 1 1 0 1 99 2 1 1 2";
 
-    assert_eq!(day_8_part_2(input), 66);
+    assert_eq!(day_8_part_2(&generator(input)), 66);
+}
+
+#[test]
+fn tree_test() {
+    let tree = generator("2 3 0 3 10 11 12 1 1 0 1 99 2 1 1 2");
+
+    assert_eq!(tree.metadata, [1, 1, 2]);
+    assert_eq!(tree.children[0].metadata, [10, 11, 12]);
+    assert_eq!(tree.children[1].children[0].metadata, [99]);
+    assert_eq!(tree.depth(), 3);
+    assert_eq!(tree.node_count(), 4);
+    assert_eq!(tree.children[1].value().unwrap(), 0);
+
+    let mut seen = Vec::new();
+    tree.walk(&mut |node: &Node, depth| seen.push((depth, node.metadata.len())));
+    assert_eq!(seen, [(0, 3), (1, 3), (1, 1), (2, 1)]);
+
+    assert_eq!(generator("1 2 0 1 5 0 3").value().unwrap(), 0);
+}
+
+#[test]
+fn parse_errors_test() {
+    assert_eq!(
+        Node::parse(""),
+        Err(ParseError::Truncated {
+            expected: "a child count"
+        })
+    );
+    assert_eq!(
+        Node::parse("1 1 0"),
+        Err(ParseError::Truncated {
+            expected: "a metadata count"
+        })
+    );
+    assert_eq!(
+        Node::parse("0 3 1 2"),
+        Err(ParseError::Truncated {
+            expected: "metadata"
+        })
+    );
+    assert_eq!(
+        Node::parse("0 1 x"),
+        Err(ParseError::InvalidNumber {
+            position: 2,
+            token: "x".to_string(),
+        })
+    );
+    assert_eq!(
+        Node::parse("0 1 7 3"),
+        Err(ParseError::TrailingInput { position: 3 })
+    );
+    assert_eq!(
+        Node::parse("0 1 4000000000").unwrap().value().unwrap(),
+        4_000_000_000
+    );
+}
+
+#[test]
+fn deep_tree_test() {
+    let depth = 200_000;
+    let input = "1 1 ".repeat(depth - 1) + "0 1 7" + &" 1".repeat(depth - 1);
+    let tree = Node::parse(&input).unwrap();
+
+    assert_eq!(tree.depth(), depth);
+    assert_eq!(tree.node_count(), depth);
+    assert_eq!(tree.metadata_sum(), 7 + depth as u64 - 1);
+    assert_eq!(tree.value().unwrap(), 7);
+}
+
+#[test]
+fn round_trip_test() {
+    let input = "2 3 0 3 10 11 12 1 1 0 1 99 2 1 1 2";
+    assert_eq!(generator(input).to_string(), input);
+
+    let shape = TreeShape {
+        max_depth: 6,
+        max_nodes: 500,
+        max_children: 4,
+        max_metadata: 5,
+        max_value: 6,
+    };
+
+    for seed in 0..50 {
+        let tree = Node::random(&shape, seed);
+
+        assert!(tree.depth() <= shape.max_depth);
+        assert!(tree.node_count() <= shape.max_nodes);
+        assert!(tree.metadata_sum() <= tree.node_count() as u64 * 5 * 6);
+        assert_eq!(Node::parse(&tree.to_string()), Ok(tree));
+    }
+
+    let deep = TreeShape {
+        max_depth: 100_000,
+        max_nodes: 100_000,
+        max_children: 1,
+        ..shape
+    };
+    let tree = Node::random(&deep, 7);
+    let numbers = tree.to_numbers();
+
+    assert_eq!(
+        Node::parse(&tree.to_string()).unwrap().to_numbers(),
+        numbers
+    );
+    assert_eq!(Node::random(&deep, 7).to_numbers(), numbers);
+}
+
+#[test]
+fn outline_test() {
+    let tree = generator("2 3 0 3 10 11 12 1 1 0 1 99 2 1 1 2");
+
+    assert_eq!(
+        tree.outline(),
+        "A: 2 children, metadata [1, 1, 2], value 66
+  B: 0 children, metadata [10, 11, 12], value 33
+  C: 1 child, metadata [2], value 0
+    D: 0 children, metadata [99], value 99
+"
+    );
+
+    assert_eq!(tree.value_at(""), Ok(66));
+    assert_eq!(tree.value_at("0"), Ok(33));
+    assert_eq!(tree.value_at("1"), Ok(0));
+    assert_eq!(tree.value_at("1/0"), Ok(99));
+    assert_eq!(
+        tree.value_at("1/1"),
+        Err(ValueError::NoSuchNode {
+            path: "1/1".to_string()
+        })
+    );
+    assert!(tree.value_at("x").is_err());
+    assert_eq!(
+        tree.at("1/0").map(|node| &node.metadata[..]),
+        Some(&[99][..])
+    );
+
+    assert_eq!(label(0), "A");
+    assert_eq!(label(25), "Z");
+    assert_eq!(label(26), "AA");
+    assert_eq!(label(27), "AB");
+    assert_eq!(label(26 + 26 * 26), "AAA");
+}
+
+#[test]
+fn value_overflow_test() {
+    // Every node counts its only child twice, doubling the leaf's 7 per level.
+    let chain = |depth: usize| {
+        let input = "1 2 ".repeat(depth - 1) + "0 1 7" + &" 1 1".repeat(depth - 1);
+        Node::parse(&input).unwrap()
+    };
+
+    assert_eq!(chain(62).value(), Ok(7 << 61));
+    assert_eq!(chain(70).value(), Err(ValueError::Overflow));
+    assert_eq!(chain(70).value_at(&"0/".repeat(67)), Ok(7 << 2));
+    assert!(chain(70)
+        .outline()
+        .starts_with("A: 1 child, metadata [1, 1], value too large for a u64\n"));
 }
@@ -1,414 +1,479 @@
 use aoc_runner_derive::aoc;
-use std::ptr::NonNull;
+use rayon::prelude::*;
+use regex::Regex;
+use std::collections::VecDeque;
 
-#[derive(Debug, Default)]
-struct Circle {
+/// A circle kept in a `Vec`, so every marble placed shifts the ones after it.
+#[derive(Debug)]
+pub struct Circle {
     backing: Vec<u32>,
     current_pos: usize,
 }
 
-impl std::ops::Deref for Circle {
-    type Target = Vec<u32>;
+/// A circle of marbles with a current marble, which is where offsets are
+/// counted from.
+pub trait Ring {
+    fn new(first: u32) -> Self;
 
-    fn deref(&self) -> &Vec<u32> {
-        &self.backing
-    }
+    /// Places `marble` just after the marble `clockwise` steps clockwise of the
+    /// current one, and makes it the current marble.
+    fn insert(&mut self, clockwise: usize, marble: u32);
+
+    /// Takes out the marble `counter_clockwise` steps counter-clockwise of the
+    /// current one. The marble after it becomes the current marble.
+    ///
+    /// Panics if it's the only marble left, as the circle can't be empty.
+    fn remove(&mut self, counter_clockwise: usize) -> u32;
+
+    fn current(&self) -> u32;
+
+    /// Every marble in clockwise order, starting with the current one.
+    fn marbles(&self) -> Vec<u32>;
 }
 
-impl std::ops::DerefMut for Circle {
-    fn deref_mut(&mut self) -> &mut Vec<u32> {
-        &mut self.backing
-    }
+/// What happened on one turn of the game.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Turn {
+    pub player: usize,
+    /// The marble played this turn. It's placed in the circle unless the turn
+    /// scored, in which case the player keeps it.
+    pub marble: u32,
+    /// The marble a scoring turn took out of the circle.
+    pub removed: Option<u32>,
+    /// What the turn added to the player's score.
+    pub score: u64,
+    /// The current marble once the turn is over.
+    pub current: u32,
 }
 
-#[aoc(day9, part1)]
-pub fn day_9_part_1(input: &str) -> u32 {
-    let players = input
-        .split_whitespace()
-        .next()
-        .unwrap()
-        .parse::<usize>()
-        .unwrap();
-    let last_marble_pts = input
-        .split_whitespace()
-        .nth(6)
-        .unwrap()
-        .parse::<u32>()
-        .unwrap();
+pub trait Observer<R> {
+    fn turn(&mut self, turn: &Turn, circle: &R);
+}
 
-    let mut marble_count = last_marble_pts;
-    let mut players: Vec<u32> = vec![0; players];
-    let mut current_player = 0;
-    let mut marble_nums = 1..;
-    let mut circle = Circle::default();
-
-    circle.push(0);
-
-    while marble_count > 0 {
-        let curr_num = marble_nums.next().unwrap();
-
-        if curr_num % 23 == 0 {
-            players[current_player] += curr_num;
-            for _ in 0..6 {
-                if circle.current_pos == 0 {
-                    circle.current_pos = circle.len() - 1;
-                } else {
-                    circle.current_pos -= 1;
-                }
-            }
-            let seven_ccw = circle.current_pos;
-            players[current_player] += circle.remove(seven_ccw);
-            circle.current_pos -= 1;
-        } else {
-            insert_clock_wise(&mut circle, 2, curr_num);
-        }
+impl<R, F: FnMut(&Turn, &R)> Observer<R> for F {
+    fn turn(&mut self, turn: &Turn, circle: &R) {
+        self(turn, circle)
+    }
+}
 
-        if current_player + 1 == players.len() {
-            current_player = 0;
-        } else {
-            current_player += 1;
+impl Ring for Circle {
+    fn new(first: u32) -> Circle {
+        Circle {
+            backing: vec![first],
+            current_pos: 0,
         }
+    }
 
-        marble_count -= 1;
+    fn insert(&mut self, clockwise: usize, marble: u32) {
+        let len = self.backing.len();
+        let pos = (self.current_pos + clockwise % len) % len + 1;
+        self.backing.insert(pos, marble);
+        self.current_pos = pos;
     }
 
-    players.into_iter().max().unwrap()
-}
+    fn remove(&mut self, counter_clockwise: usize) -> u32 {
+        let len = self.backing.len();
+        assert!(len > 1, "can't remove the last marble in the circle");
+        let pos = (self.current_pos + len - counter_clockwise % len) % len;
+        let marble = self.backing.remove(pos);
+        self.current_pos = pos % self.backing.len();
 
-fn insert_clock_wise(c: &mut Circle, positions_clockwise: usize, marble_num: u32) {
-    let pos = (c.current_pos + positions_clockwise) % c.len();
-    c.insert(pos + 1, marble_num);
-    c.current_pos = pos;
+        marble
+    }
+
+    fn current(&self) -> u32 {
+        self.backing[self.current_pos]
+    }
+
+    fn marbles(&self) -> Vec<u32> {
+        let (before, after) = self.backing.split_at(self.current_pos);
+        after.iter().chain(before).cloned().collect()
+    }
 }
 
-#[derive(Default)]
-struct Circle2 {
-    backing: LinkedList,
-    current_pos: Option<NonNull<Node>>,
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GameError {
+    /// The input isn't a "N players; last marble is worth M points" line, or
+    /// its numbers don't fit.
+    Malformed { input: String },
+    /// Scaling the last marble by `multiplier` doesn't fit in a `u32` marble.
+    TooManyMarbles { last_marble: u32, multiplier: u32 },
+    /// Marbles are scored when divisible by `divisor`, so below 2 a scoring
+    /// turn can find the circle empty.
+    InvalidDivisor { divisor: u32 },
 }
 
-impl std::fmt::Debug for Circle2 {
+impl std::fmt::Display for GameError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        let mut curr_node = self.head;
-
-        write!(f, "[")?;
-        while curr_node.is_some() {
-            unsafe {
-                if curr_node == self.current_pos {
-                    write!(f, "({}), ", curr_node.unwrap().as_ref().value)?;
-                } else {
-                    write!(f, "{}, ", curr_node.unwrap().as_ref().value)?;
-                }
-
-                curr_node = curr_node.unwrap().as_ref().next;
+        match self {
+            GameError::Malformed { input } => write!(f, "malformed game: {:?}", input),
+            GameError::TooManyMarbles {
+                last_marble,
+                multiplier,
+            } => write!(
+                f,
+                "{} marbles times {} doesn't fit in a u32",
+                last_marble, multiplier
+            ),
+            GameError::InvalidDivisor { divisor } => {
+                write!(f, "scoring divisor must be at least 2, got {}", divisor)
             }
         }
-        write!(f, "]")?;
-
-        Ok(())
     }
 }
 
-impl std::ops::Deref for Circle2 {
-    type Target = LinkedList;
+impl std::error::Error for GameError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MarbleGame {
+    pub players: usize,
+    pub last_marble: u32,
+    /// Marbles divisible by this are scored instead of placed.
+    pub scoring_divisor: u32,
+    /// How far counter-clockwise of the current marble a scoring turn takes
+    /// its extra marble from.
+    pub removal_offset: usize,
+    /// How many marbles clockwise of the current marble the next one is
+    /// placed after.
+    pub insert_offset: usize,
+}
 
-    fn deref(&self) -> &LinkedList {
-        &self.backing
+impl MarbleGame {
+    /// The game with the puzzle's rules.
+    pub fn new(players: usize, last_marble: u32) -> MarbleGame {
+        MarbleGame {
+            players,
+            last_marble,
+            scoring_divisor: 23,
+            removal_offset: 7,
+            insert_offset: 1,
+        }
     }
-}
 
-impl std::ops::DerefMut for Circle2 {
-    fn deref_mut(&mut self) -> &mut LinkedList {
-        &mut self.backing
+    pub fn parse(input: &str) -> Result<MarbleGame, GameError> {
+        let regex = Regex::new(r"(\d+) players; last marble is worth (\d+) points").unwrap();
+        let malformed = || GameError::Malformed {
+            input: input.to_string(),
+        };
+        let captures = regex.captures(input).ok_or_else(malformed)?;
+
+        Ok(MarbleGame::new(
+            captures[1].parse().map_err(|_| malformed())?,
+            captures[2].parse().map_err(|_| malformed())?,
+        ))
     }
-}
 
-// Through me you pass into the city of woe: 
-// Through me you pass into eternal pain: 
-// Through me among the people lost for aye. 
-// Justice the founder of my fabric moved: 
-// To rear me was the task of Power divine,        
-// Supremest Wisdom, and primeval Love.
-// Before me things create were none, save things 
-// Eternal, and eternal I endure. 
-// Abandon all hope, ye who enter here.
-
-#[derive(Default)]
-struct LinkedList {
-    len: usize,
-    head: Option<NonNull<Node>>,
-    tail: Option<NonNull<Node>>,
-}
+    /// The same game played out to `multiplier` times as many marbles.
+    pub fn scaled(&self, multiplier: u32) -> Result<MarbleGame, GameError> {
+        let last_marble =
+            self.last_marble
+                .checked_mul(multiplier)
+                .ok_or(GameError::TooManyMarbles {
+                    last_marble: self.last_marble,
+                    multiplier,
+                })?;
+
+        Ok(MarbleGame {
+            last_marble,
+            ..*self
+        })
+    }
 
-#[derive(Debug)]
-struct Node {
-    prev: Option<NonNull<Node>>,
-    next: Option<NonNull<Node>>,
-    value: u32,
-}
+    /// Checks the rules can be played: with a scoring divisor of at least 2,
+    /// every scoring turn follows a placing one, so the circle never empties.
+    pub fn validate(&self) -> Result<(), GameError> {
+        if self.scoring_divisor < 2 {
+            return Err(GameError::InvalidDivisor {
+                divisor: self.scoring_divisor,
+            });
+        }
 
-impl LinkedList {
-    #[allow(dead_code)]
-    fn new() -> LinkedList {
-        Self::default()
+        Ok(())
     }
 
-    fn push_back(&mut self, value: u32) -> NonNull<Node> {
-        self.len += 1;
-        let mut ptr = NonNull::new(Box::into_raw(Box::new(Node {
-            prev: None,
-            next: None,
-            value,
-        })))
-        .unwrap();
+    /// Plays every marble and returns each player's score. Every marble is
+    /// scored at most once, so the total stays below `u32::MAX²` and a `u64`
+    /// score can't overflow.
+    pub fn play<R: Ring>(&self) -> Result<Vec<u64>, GameError> {
+        self.play_with::<R>(&mut |_: &Turn, _: &R| ())
+    }
 
-        if self.tail.is_none() {
-            self.tail = Some(ptr);
-            unsafe {
-                self.head.unwrap().as_mut().next = self.tail;
-            }
-        } else {
-            unsafe {
-                ptr.as_mut().prev = self.tail;
-                self.tail.unwrap().as_mut().next = Some(ptr);
+    /// Like `play`, but shows the observer every turn and the circle it left.
+    pub fn play_with<R: Ring>(
+        &self,
+        observer: &mut impl Observer<R>,
+    ) -> Result<Vec<u64>, GameError> {
+        self.validate()?;
+
+        let mut scores = vec![0; self.players];
+        let mut circle = R::new(0);
+
+        for (marble, player) in (1..=self.last_marble).zip((0..self.players).cycle()) {
+            let mut removed = None;
+            let mut score = 0;
+            if marble % self.scoring_divisor == 0 {
+                let taken = circle.remove(self.removal_offset);
+                removed = Some(taken);
+                score = u64::from(marble) + u64::from(taken);
+                scores[player] += score;
+            } else {
+                circle.insert(self.insert_offset, marble);
             }
-            self.tail = Some(ptr);
+
+            let turn = Turn {
+                player,
+                marble,
+                removed,
+                score,
+                current: circle.current(),
+            };
+            observer.turn(&turn, &circle);
         }
 
-        self.tail.unwrap()
+        Ok(scores)
     }
 
-    fn push_front(&mut self, value: u32) -> NonNull<Node> {
-        self.len += 1;
-        let mut ptr = NonNull::new(Box::into_raw(Box::new(Node {
-            prev: None,
-            next: None,
-            value,
-        })))
-        .unwrap();
+    /// The circle after each of the first `turns` turns, laid out like the
+    /// puzzle's example: players numbered from 1, marbles starting from the
+    /// lowest one and the current marble in brackets.
+    pub fn transcript(&self, turns: u32) -> Result<String, GameError> {
+        let game = MarbleGame {
+            last_marble: self.last_marble.min(turns),
+            ..*self
+        };
+        let mut lines = vec![render_turn("-", &[0], 0)];
+        game.play_with(&mut |turn: &Turn, circle: &Circle| {
+            let label = (turn.player + 1).to_string();
+            lines.push(render_turn(&label, &circle.marbles(), turn.current));
+        })?;
+
+        Ok(lines.join("\n"))
+    }
+}
 
-        if self.head.is_none() {
-            self.head = Some(ptr);
+fn render_turn(label: &str, marbles: &[u32], current: u32) -> String {
+    let lowest = (0..marbles.len())
+        .min_by_key(|&idx| marbles[idx])
+        .unwrap_or(0);
+    let (before, after) = marbles.split_at(lowest);
+
+    let mut line = format!("[{}]", label);
+    let mut after_current = false;
+    for &marble in after.iter().chain(before) {
+        if marble == current {
+            line += &format!("{:>3})", format!("({}", marble));
+        } else if after_current {
+            line += &format!("{:>2}", marble);
         } else {
-            unsafe {
-                ptr.as_mut().next = self.head;
-                self.head.unwrap().as_mut().prev = Some(ptr);
-            }
-            self.head = Some(ptr);
+            line += &format!("{:>3}", marble);
         }
-
-        self.head.unwrap()
+        after_current = marble == current;
     }
 
-    fn push_after(&mut self, mut after: NonNull<Node>, value: u32) -> NonNull<Node> {
-        unsafe {
-            if after.as_ref().next.is_none() {
-                self.push_back(value);
-            } else {
-                self.len += 1;
-                let ptr = NonNull::new(Box::into_raw(Box::new(Node {
-                    prev: Some(after),
-                    next: after.as_ref().next,
-                    value,
-                })))
-                .unwrap();
+    line
+}
 
-                if after.as_ref().next.is_some() {
-                    after.as_ref().next.unwrap().as_mut().prev = Some(ptr);
-                }
-                after.as_mut().next = Some(ptr);
+/// The winning score with the input's last marble scaled by `multiplier`.
+pub fn high_score<R: Ring>(input: &str, multiplier: u32) -> Result<u64, GameError> {
+    let scores = MarbleGame::parse(input)?.scaled(multiplier)?.play::<R>()?;
 
-                //ptr
-            }
-        }
+    Ok(scores.into_iter().max().unwrap_or(0))
+}
 
-        after
-    }
+/// Who won one game of a sweep.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Winner {
+    pub players: usize,
+    pub last_marble: u32,
+    /// Index of the winning player. Ties go to the earliest player.
+    pub player: usize,
+    pub score: u64,
+}
 
-    #[allow(dead_code)]
-    fn push_before(&mut self, mut after: NonNull<Node>, value: u32) -> NonNull<Node> {
-        unsafe {
-            if after.as_ref().next.is_none() {
-                self.push_back(value)
-            } else if after.as_ref().prev.is_none() {
-                self.push_front(value)
-            } else {
-                self.len += 1;
-                let ptr = NonNull::new(Box::into_raw(Box::new(Node {
-                    prev: after.as_ref().prev,
-                    next: Some(after),
-                    value,
-                })))
+/// Plays `base` with every combination of player count and last marble, in
+/// parallel, returning the winners ordered by player count then last marble.
+/// A player count of zero has no winner and is skipped, and rules that
+/// can't be played are an error.
+pub fn sweep(
+    base: &MarbleGame,
+    players: &[usize],
+    last_marbles: &[u32],
+) -> Result<Vec<Winner>, GameError> {
+    let games: Vec<_> = players
+        .iter()
+        .flat_map(|&players| {
+            last_marbles
+                .iter()
+                .map(move |&last_marble| (players, last_marble))
+        })
+        .collect();
+
+    games
+        .into_par_iter()
+        .filter(|&(players, _)| players > 0)
+        .map(|(players, last_marble)| {
+            let game = MarbleGame {
+                players,
+                last_marble,
+                ..*base
+            };
+            let scores = game.play::<Deque>()?;
+            let (player, &score) = scores
+                .iter()
+                .enumerate()
+                .rev()
+                .max_by_key(|&(_, score)| score)
                 .unwrap();
 
-                after.as_ref().prev.unwrap().as_mut().next = Some(ptr);
-                after.as_mut().prev = Some(ptr);
+            Ok(Winner {
+                players,
+                last_marble,
+                player,
+                score,
+            })
+        })
+        .collect()
+}
 
-                ptr
-            }
-        }
+/// One CSV row per winner, under a header row.
+pub fn winners_csv(winners: &[Winner]) -> String {
+    use std::fmt::Write;
+
+    let mut out = String::from("players,last_marble,winner_index,winning_score\n");
+    for winner in winners {
+        writeln!(
+            out,
+            "{},{},{},{}",
+            winner.players, winner.last_marble, winner.player, winner.score
+        )
+        .unwrap();
     }
 
-    fn remove(&mut self, node: NonNull<Node>) -> Option<u32> {
-        unsafe {
-            if node.as_ref().prev.is_none() {
-                self.pop_front()
-            } else if node.as_ref().next.is_none() {
-                self.pop_back()
-            } else {
-                let ptr = node.as_ref().next;
-                let ptr2 = node.as_ref().prev;
-
-                ptr.unwrap().as_mut().prev = ptr2;
-                ptr2.unwrap().as_mut().next = ptr;
-                let ret = node.as_ref().value;
+    out
+}
 
-                Box::from_raw(node.as_ptr());
-                Some(ret)
-            }
-        }
-    }
+#[aoc(day9, part1)]
+pub fn day_9_part_1(input: &str) -> u64 {
+    high_score::<Circle>(input, 1).unwrap()
+}
 
-    fn pop_back(&mut self) -> Option<u32> {
-        if self.tail.is_none() {
-            unsafe {
-                let ptr = self.head?;
-                let ret = ptr.as_ref().value;
-                self.head = None;
+/// Keeps the current marble at the back, so every move is a rotation.
+#[derive(Debug)]
+pub struct Deque(VecDeque<u32>);
 
-                Box::from_raw(ptr.as_ptr());
+impl Ring for Deque {
+    fn new(first: u32) -> Deque {
+        Deque(VecDeque::from(vec![first]))
+    }
 
-                Some(ret)
-            }
-        } else {
-            unsafe {
-                let mut tail_prev = self.tail.unwrap().as_mut().prev?;
-                tail_prev.as_mut().next = None;
-                let ptr = self.tail.unwrap();
-                self.tail = self.tail.unwrap().as_mut().prev;
+    fn insert(&mut self, clockwise: usize, marble: u32) {
+        let len = self.0.len();
+        self.0.rotate_left(clockwise % len);
+        self.0.push_back(marble);
+    }
 
-                let ret = ptr.as_ref().value;
-                Box::from_raw(ptr.as_ptr());
+    fn remove(&mut self, counter_clockwise: usize) -> u32 {
+        let len = self.0.len();
+        assert!(len > 1, "can't remove the last marble in the circle");
+        self.0.rotate_right(counter_clockwise % len);
+        let marble = self.0.pop_back().unwrap();
+        self.0.rotate_left(1);
 
-                Some(ret)
-            }
-        }
+        marble
     }
 
-    fn pop_front(&mut self) -> Option<u32> {
-        if self.head.is_none() {
-            unsafe {
-                let ptr = self.tail?;
-                let ret = ptr.as_ref().value;
-                self.tail = None;
-
-                Box::from_raw(ptr.as_ptr());
+    fn current(&self) -> u32 {
+        *self.0.back().unwrap()
+    }
 
-                Some(ret)
-            }
-        } else {
-            unsafe {
-                let ptr = self.head.unwrap();
+    fn marbles(&self) -> Vec<u32> {
+        let rest = self.0.iter().take(self.0.len() - 1);
+        self.0.back().into_iter().chain(rest).cloned().collect()
+    }
+}
 
-                if let Some(mut head_next) = self.head.unwrap().as_ref().next {
-                    head_next.as_mut().prev = None;
-                    self.head = self.head.unwrap().as_ref().next;
-                }
+/// A doubly linked ring kept as `next`/`prev` tables indexed by marble.
+#[derive(Debug)]
+pub struct Links {
+    next: Vec<u32>,
+    prev: Vec<u32>,
+    current: u32,
+    len: usize,
+}
 
-                let ret = ptr.as_ref().value;
-                Box::from_raw(ptr.as_ptr());
+impl Ring for Links {
+    fn new(first: u32) -> Links {
+        let len = first as usize + 1;
 
-                Some(ret)
-            }
+        Links {
+            next: vec![first; len],
+            prev: vec![first; len],
+            current: first,
+            len: 1,
         }
     }
 
-    #[allow(dead_code)]
-    fn len(&self) -> usize {
-        self.len
-    }
-}
+    fn insert(&mut self, clockwise: usize, marble: u32) {
+        let mut at = self.current;
+        for _ in 0..clockwise % self.len {
+            at = self.next[at as usize];
+        }
 
-impl std::ops::Drop for LinkedList {
-    fn drop(&mut self) {
-        while self.head != None {
-            self.pop_front();
+        if marble as usize >= self.next.len() {
+            self.next.resize(marble as usize + 1, 0);
+            self.prev.resize(marble as usize + 1, 0);
         }
+
+        let after = self.next[at as usize];
+        self.next[at as usize] = marble;
+        self.prev[marble as usize] = at;
+        self.next[marble as usize] = after;
+        self.prev[after as usize] = marble;
+        self.current = marble;
+        self.len += 1;
     }
-}
 
-#[aoc(day9, part2)]
-pub fn day_9_part_2(input: &str) -> u32 {
-    let players = input
-        .split_whitespace()
-        .next()
-        .unwrap()
-        .parse::<usize>()
-        .unwrap();
-    let last_marble_pts = input
-        .split_whitespace()
-        .nth(6)
-        .unwrap()
-        .parse::<u32>()
-        .unwrap();
+    fn remove(&mut self, counter_clockwise: usize) -> u32 {
+        assert!(self.len > 1, "can't remove the last marble in the circle");
 
-    let mut marble_count = last_marble_pts * 100;
-    let mut players: Vec<u32> = vec![0; players];
-    let mut current_player = 0;
-    let mut marble_nums = 1..;
-    let mut circle = Circle2::default();
-    circle.push_front(0);
-    circle.current_pos = circle.head;
-
-    while marble_count > 0 {
-        let curr_num = marble_nums.next().unwrap();
-
-        if curr_num % 23 == 0 {
-            players[current_player] += curr_num;
-            for _ in 0..6 {
-                unsafe {
-                    if circle.current_pos.unwrap().as_ref().prev.is_none() {
-                        circle.current_pos = circle.tail;
-                    } else {
-                        circle.current_pos = circle.current_pos.unwrap().as_ref().prev;
-                    }
-                }
-            }
-            let seven_ccw = circle.current_pos;
-            unsafe {
-                circle.current_pos = circle.current_pos.unwrap().as_ref().prev;
-            }
-            players[current_player] += circle.remove(seven_ccw.unwrap()).unwrap();
-        } else {
-            insert_clock_wise2(&mut circle, 2, curr_num);
+        let mut at = self.current;
+        for _ in 0..counter_clockwise % self.len {
+            at = self.prev[at as usize];
         }
 
-        if current_player + 1 == players.len() {
-            current_player = 0;
-        } else {
-            current_player += 1;
-        }
+        let (prev, next) = (self.prev[at as usize], self.next[at as usize]);
+        self.next[prev as usize] = next;
+        self.prev[next as usize] = prev;
+        self.current = next;
+        self.len -= 1;
 
-        marble_count -= 1;
+        at
     }
 
-    players.into_iter().max().unwrap()
-}
+    fn current(&self) -> u32 {
+        self.current
+    }
 
-fn insert_clock_wise2(circle: &mut Circle2, positions_clockwise: usize, marble_num: u32) {
-    for _ in 0..positions_clockwise {
-        unsafe {
-            if circle.current_pos.unwrap().as_ref().next.is_none() {
-                circle.current_pos = circle.head;
-            } else {
-                circle.current_pos = circle.current_pos.unwrap().as_ref().next;
-            }
+    fn marbles(&self) -> Vec<u32> {
+        let mut marbles = vec![self.current];
+        let mut at = self.next[self.current as usize];
+        while at != self.current {
+            marbles.push(at);
+            at = self.next[at as usize];
         }
+
+        marbles
     }
+}
 
-    let node = circle.current_pos.unwrap();
+#[aoc(day9, part2)]
+pub fn day_9_part_2(input: &str) -> u64 {
+    high_score::<Deque>(input, 100).unwrap()
+}
 
-    circle.current_pos = Some(circle.push_after(node, marble_num));
+#[aoc(day9, part2, links)]
+pub fn day_9_part_2_links(input: &str) -> u64 {
+    high_score::<Links>(input, 100).unwrap()
 }
 
 #[test]
@@ -424,9 +489,235 @@ fn day_9_test() {
 #[test]
 fn day_9_test_2() {
     let hs = day_9_part_2("9 players; last marble is worth 25 points");
-    assert_eq!(hs, 32);
+    assert_eq!(hs, 22563);
     let hs = day_9_part_2("10 players; last marble is worth 1618 points");
-    assert_eq!(hs, 8317);
+    assert_eq!(hs, 74_765_078);
     let hs = day_9_part_2("13 players; last marble is worth 7999 points");
-    assert_eq!(hs, 146_373);
+    assert_eq!(hs, 1_406_506_154);
+    let hs = day_9_part_2_links("10 players; last marble is worth 1618 points");
+    assert_eq!(hs, 74_765_078);
+    let hs = day_9_part_2_links("13 players; last marble is worth 7999 points");
+    assert_eq!(hs, 1_406_506_154);
+}
+
+#[test]
+fn marble_game_test() {
+    let game = MarbleGame::new(9, 25);
+    let scores = game.play::<Circle>().unwrap();
+
+    assert_eq!(scores.len(), 9);
+    assert_eq!(scores[4], 32);
+    assert_eq!(scores.iter().sum::<u64>(), 32);
+    assert_eq!(
+        MarbleGame::parse("9 players; last marble is worth 25 points"),
+        Ok(game)
+    );
+    for input in &[
+        "9 players",
+        "9 players; last marble is worth 4294967296 points",
+    ] {
+        assert_eq!(
+            MarbleGame::parse(input),
+            Err(GameError::Malformed {
+                input: input.to_string()
+            })
+        );
+        assert!(high_score::<Deque>(input, 1).is_err());
+    }
+
+    let no_scoring = MarbleGame {
+        scoring_divisor: u32::MAX,
+        ..MarbleGame::new(3, 100)
+    };
+    assert_eq!(no_scoring.play::<Circle>().unwrap(), [0, 0, 0]);
+
+    let house_rules = MarbleGame {
+        scoring_divisor: 5,
+        removal_offset: 2,
+        insert_offset: 0,
+        ..MarbleGame::new(2, 10)
+    };
+    // 0 1 2 3 (4) | 5 takes 2 -> 0 1 (3) 4 | 0 1 3 6 7 8 (9) 4 | 10 takes 7
+    assert_eq!(house_rules.play::<Circle>().unwrap(), [5 + 2, 10 + 7]);
+
+    for divisor in 0..2 {
+        let game = MarbleGame {
+            scoring_divisor: divisor,
+            ..MarbleGame::new(2, 10)
+        };
+        let error = GameError::InvalidDivisor { divisor };
+
+        assert_eq!(game.play::<Circle>().unwrap_err(), error);
+        assert_eq!(game.transcript(5).unwrap_err(), error);
+        assert_eq!(sweep(&game, &[1, 2], &[5]).unwrap_err(), error);
+    }
+}
+
+#[test]
+fn rings_agree_test() {
+    let games = [
+        MarbleGame::new(9, 25),
+        MarbleGame::new(10, 1618),
+        MarbleGame::new(13, 7999),
+        MarbleGame {
+            scoring_divisor: 5,
+            removal_offset: 2,
+            insert_offset: 0,
+            ..MarbleGame::new(2, 500)
+        },
+        MarbleGame {
+            scoring_divisor: 3,
+            removal_offset: 11,
+            insert_offset: 4,
+            ..MarbleGame::new(7, 500)
+        },
+        // Offsets far beyond the circle's size wrap round it.
+        MarbleGame {
+            removal_offset: usize::MAX,
+            insert_offset: usize::MAX - 1,
+            ..MarbleGame::new(5, 2000)
+        },
+        // Every other turn takes the circle back down to a single marble.
+        MarbleGame {
+            scoring_divisor: 2,
+            removal_offset: 3,
+            ..MarbleGame::new(3, 100)
+        },
+        MarbleGame {
+            scoring_divisor: 1,
+            ..MarbleGame::new(2, 10)
+        },
+        MarbleGame {
+            scoring_divisor: 0,
+            ..MarbleGame::new(2, 10)
+        },
+    ];
+
+    for game in &games {
+        let expected = game.play::<Circle>();
+        assert_eq!(game.play::<Deque>(), expected, "{:?}", game);
+        assert_eq!(game.play::<Links>(), expected, "{:?}", game);
+    }
+
+    fn removes_last_marble<R: Ring>() -> bool {
+        std::panic::catch_unwind(|| R::new(0).remove(0)).is_ok()
+    }
+
+    assert!(!removes_last_marble::<Circle>());
+    assert!(!removes_last_marble::<Deque>());
+    assert!(!removes_last_marble::<Links>());
+}
+
+#[test]
+fn scaled_game_test() {
+    let input = "10 players; last marble is worth 1618 points";
+    let game = MarbleGame::parse(input).unwrap();
+
+    assert_eq!(game.scaled(100).unwrap().last_marble, 161_800);
+    assert_eq!(
+        game.scaled(u32::MAX),
+        Err(GameError::TooManyMarbles {
+            last_marble: 1618,
+            multiplier: u32::MAX
+        })
+    );
+
+    // Past u32::MAX, where the old u32 scores wrapped around.
+    let hs = high_score::<Deque>(input, 1000).unwrap();
+    assert_eq!(hs, high_score::<Links>(input, 1000).unwrap());
+    assert_eq!(hs, 7_466_417_491);
+}
+
+#[test]
+fn transcript_test() {
+    let transcript = MarbleGame::new(9, 25).transcript(25).unwrap();
+    let lines: Vec<_> = transcript.lines().collect();
+
+    assert_eq!(lines.len(), 26);
+    assert_eq!(lines[0], "[-] (0)");
+    assert_eq!(lines[1], "[1]  0 (1)");
+    assert_eq!(lines[2], "[2]  0 (2) 1");
+    assert_eq!(lines[4], "[4]  0 (4) 2  1  3");
+    assert_eq!(lines[10], "[1]  0  8  4  9  2(10) 5  1  6  3  7");
+    assert_eq!(
+        lines[23],
+        "[5]  0 16  8 17  4 18(19) 2 20 10 21  5 22 11  1 12  6 13  3 14  7 15"
+    );
+    assert_eq!(
+        lines[25],
+        "[7]  0 16  8 17  4 18 19  2 24 20(25)10 21  5 22 11  1 12  6 13  3 14  7 15"
+    );
+    assert_eq!(
+        MarbleGame::new(9, 25)
+            .transcript(2)
+            .unwrap()
+            .lines()
+            .count(),
+        3
+    );
+}
+
+#[test]
+fn observer_test() {
+    let game = MarbleGame::new(9, 25);
+    let mut turns = Vec::new();
+    let scores = game
+        .play_with(&mut |turn: &Turn, circle: &Deque| {
+            assert_eq!(circle.marbles()[0], turn.current);
+            turns.push(*turn);
+        })
+        .unwrap();
+
+    assert_eq!(turns.len(), 25);
+    assert_eq!(
+        turns[22],
+        Turn {
+            player: 4,
+            marble: 23,
+            removed: Some(9),
+            score: 32,
+            current: 19,
+        }
+    );
+    assert_eq!(turns.iter().map(|turn| turn.score).sum::<u64>(), 32);
+    assert_eq!(scores, game.play::<Links>().unwrap());
+
+    for game in &[MarbleGame::new(13, 300), MarbleGame::new(7, 99)] {
+        let mut circles = (Vec::new(), Vec::new());
+        game.play_with(&mut |_: &Turn, circle: &Circle| circles.0.push(circle.marbles()))
+            .unwrap();
+        game.play_with(&mut |_: &Turn, circle: &Links| circles.1.push(circle.marbles()))
+            .unwrap();
+        assert_eq!(circles.0, circles.1);
+    }
+}
+
+#[test]
+fn sweep_test() {
+    let winners = sweep(&MarbleGame::new(0, 0), &[9, 10, 13, 0], &[25, 1618, 7999]).unwrap();
+
+    assert_eq!(winners.len(), 9);
+    assert_eq!(
+        winners[0],
+        Winner {
+            players: 9,
+            last_marble: 25,
+            player: 4,
+            score: 32,
+        }
+    );
+    assert_eq!(winners[4].score, 8317);
+    assert_eq!(winners[8].score, 146_373);
+
+    // Nobody scores before marble 23, so the first player wins the tie.
+    let winners = sweep(&MarbleGame::new(0, 0), &[2, 3], &[5, 23]).unwrap();
+    assert_eq!(
+        winners_csv(&winners),
+        "players,last_marble,winner_index,winning_score
+2,5,0,0
+2,23,0,32
+3,5,0,0
+3,23,1,32
+"
+    );
 }